@@ -0,0 +1,192 @@
+use crate::hllpp::*;
+use crate::universalhash::hasher::SketchHasher;
+use crate::universalhash::univesalhash::UniversalHashFunction;
+use crate::universalhash::tabulation::*;
+use crate::universalhash::polynomial::{reduce, PolynomialHashFunction, MERSENNE_PRIME};
+use crate::universalhash::{murmur3::MurmurHash3, xxhash::XxHash64, wyhash::WyHash, siphash::SipHash24};
+use rand::prelude::*;
+use rand::rngs::StdRng;
+
+// an attacker tries to bias a HLLPP<14, 25> that already counted 100k legit users (so it is in the normal format)
+// deflation: 1m extra items whose rank is 1, they never beat a non empty register so they are not counted
+// inflation: 16k extra items whose rank is at least 8, they push most registers far above where 116k items would put them
+// both are crafted by trying random items against the attacker's hash function, which is
+// - the victim's one when the seeds are known
+// - a freshly seeded one of the same family when the seeds are secret
+// - one rebuilt from (item, hash) pairs of the victim's function when its outputs leak, this only works for
+//   families whose outputs give their seeds away, the others fall back to a freshly seeded one
+// the structured attack needs no hash function at all, it just sends 1m items with a stride of 2^40
+// every line prints estimate / true count, so 1 means the attack did nothing
+// every family falls to crafted items once its seeds are known, what siphash adds is that it is a prf:
+// watching its outputs does not reveal the key, while linear families like multiply-shift, polynomials or simple
+// tabulation give their seeds away to anyone who sees a few (item, hash) pairs,
+// and murmur/xxhash/wyhash often run with public default seeds
+pub fn benchmark_adversarial_with_hasher<H: LeakedOutputs>(hasher_name: &str, master_seed: u64) {
+    let mut seeds = StdRng::seed_from_u64(master_seed);
+    const NUM_LEGIT: usize = 100_000;
+    let mut rng = StdRng::seed_from_u64(seeds.random());
+    let legit: Vec<u64> = (0..NUM_LEGIT).map(|_| rng.random()).collect();
+    let victim_hasher = H::from_seed(seeds.random());
+    let run_attack = |attack: &[u64]| -> f64 {
+        let mut hllpp = hllpp::HLLPP::<14,25,H>::with_hash_function(victim_hasher.clone());
+        for &data in legit.iter().chain(attack.iter()) {
+            hllpp.read_data(data);
+        }
+        hllpp.compute_estimates() / (NUM_LEGIT + attack.len()) as f64
+    };
+    let is_deflating = |hashed_data: u64| (hashed_data << 14).leading_zeros() == 0;
+    let is_inflating = |hashed_data: u64| (hashed_data << 14).leading_zeros() >= 7;
+    let secret_guess = H::from_seed(seeds.random());
+    let recovered = H::recover(&|data| victim_hasher.hash64(data));
+    let leaked_guess = recovered.clone().unwrap_or_else(|| H::from_seed(seeds.random()));
+    let structured: Vec<u64> = (1..=1_000_000u64).map(|i| i << 40).collect();
+    println!(
+        "{}: no attack {}, deflation with known seeds {}, with secret seeds {}, with leaked outputs {}, inflation with known seeds {}, with secret seeds {}, with leaked outputs {}, structured {}, recovered from outputs {}",
+        hasher_name,
+        run_attack(&[]),
+        run_attack(&craft(&victim_hasher, 1_000_000, is_deflating, &mut rng)),
+        run_attack(&craft(&secret_guess, 1_000_000, is_deflating, &mut rng)),
+        run_attack(&craft(&leaked_guess, 1_000_000, is_deflating, &mut rng)),
+        run_attack(&craft(&victim_hasher, 1 << 14, is_inflating, &mut rng)),
+        run_attack(&craft(&secret_guess, 1 << 14, is_inflating, &mut rng)),
+        run_attack(&craft(&leaked_guess, 1 << 14, is_inflating, &mut rng)),
+        run_attack(&structured),
+        recovered.is_some(),
+    );
+}
+
+pub fn benchmark_adversarial_hash_families(master_seed: u64) {
+    benchmark_adversarial_with_hasher::<UniversalHashFunction>("multiply-shift", master_seed);
+    benchmark_adversarial_with_hasher::<SimpleTabulationHashFunction>("simple tabulation", master_seed);
+    benchmark_adversarial_with_hasher::<TwistedTabulationHashFunction>("twisted tabulation", master_seed);
+    benchmark_adversarial_with_hasher::<PolynomialHashFunction<2>>("polynomial k = 2", master_seed);
+    benchmark_adversarial_with_hasher::<PolynomialHashFunction<4>>("polynomial k = 4", master_seed);
+    benchmark_adversarial_with_hasher::<MurmurHash3>("murmurhash3", master_seed);
+    benchmark_adversarial_with_hasher::<XxHash64>("xxhash64", master_seed);
+    benchmark_adversarial_with_hasher::<WyHash>("wyhash", master_seed);
+    benchmark_adversarial_with_hasher::<SipHash24>("siphash-2-4", master_seed);
+}
+
+fn craft<H: SketchHasher>(hasher: &H, num_items: usize, is_wanted: impl Fn(u64) -> bool, rng: &mut impl Rng) -> Vec<u64> {
+    let mut crafted = Vec::with_capacity(num_items);
+    while crafted.len() < num_items {
+        let candidate = rng.random();
+        if is_wanted(hasher.hash64(candidate)) {
+            crafted.push(candidate);
+        }
+    }
+    crafted
+}
+
+// what an attacker learns from the victim's outputs, it gets to hash items of its choice
+// and rebuilds a function that hashes (close to) like the victim's one, none when the outputs don't give the seeds away
+pub trait LeakedOutputs: SketchHasher {
+    fn recover(_observe: &dyn Fn(u64) -> u64) -> Option<Self> {
+        None
+    }
+}
+
+impl LeakedOutputs for UniversalHashFunction {
+    // h(x) = ((a x + b) mod 2^128) >> 64, so h(0) is the top half of b, h(2^63) - h(0) is bits 1..64 of a
+    // and h(1) - h(0) the top half of a, each up to a carry out of the unknown low half of b
+    // the low bits of a only move the output by a few units, a wrong top half of a moves it by x,
+    // so a handful of extra pairs tell which carry was the right one
+    fn recover(observe: &dyn Fn(u64) -> u64) -> Option<Self> {
+        let hashed_zero = observe(0);
+        let a_low = (observe(1 << 63).wrapping_sub(hashed_zero) << 1) | 1;
+        let a_high = observe(1).wrapping_sub(hashed_zero);
+        let checks: Vec<u64> = (1..=8u64).map(|i| i.wrapping_mul(0x9e3779b97f4a7c15)).collect();
+        [a_high, a_high.wrapping_sub(1)].into_iter()
+            .map(|a_high| UniversalHashFunction::new(64, ((a_high as u128) << 64) | a_low as u128, (hashed_zero as u128) << 64))
+            .find(|candidate| checks.iter().all(|&data| {
+                let error = candidate.hash64(data).wrapping_sub(observe(data));
+                error.min(error.wrapping_neg()) <= 4
+            }))
+    }
+}
+
+impl<const K: usize> LeakedOutputs for PolynomialHashFunction<K> {
+    // the outputs are a degree k - 1 polynomial mod p shifted up by 3 bits,
+    // so k pairs fix its coefficients by lagrange interpolation
+    fn recover(observe: &dyn Fn(u64) -> u64) -> Option<Self> {
+        let mul = |a: u64, b: u64| reduce(a as u128 * b as u128);
+        let sub = |a: u64, b: u64| reduce(a as u128 + (MERSENNE_PRIME - b) as u128);
+        let inverse = |a: u64| (0..61).fold((1u64, a), |(inverse, power), bit| {
+            // p - 2 = 2^61 - 3 has every bit but bit 1 set
+            (if bit == 1 { inverse } else { mul(inverse, power) }, mul(power, power))
+        }).0;
+        let mut coefficients = [0u64; K];
+        for point in 0..K as u64 {
+            let hashed = observe(point) >> 3;
+            // basis polynomial prod_{j != point} (x - j) / (point - j)
+            let mut basis = vec![1u64];
+            let mut denominator = 1u64;
+            for other_point in (0..K as u64).filter(|&other_point| other_point != point) {
+                let mut next = vec![0u64; basis.len() + 1];
+                for (power, &coefficient) in basis.iter().enumerate() {
+                    next[power + 1] = reduce(next[power + 1] as u128 + coefficient as u128);
+                    next[power] = sub(next[power], mul(coefficient, other_point));
+                }
+                basis = next;
+                denominator = mul(denominator, sub(point, other_point));
+            }
+            let scale = mul(hashed, inverse(denominator));
+            for (coefficient, &basis_coefficient) in coefficients.iter_mut().zip(basis.iter()) {
+                *coefficient = reduce(*coefficient as u128 + mul(scale, basis_coefficient) as u128);
+            }
+        }
+        Some(PolynomialHashFunction::new(coefficients))
+    }
+}
+
+impl LeakedOutputs for SimpleTabulationHashFunction {
+    // the hash is the xor of one entry per byte, so 8 * 256 keys with a single non zero byte give tables
+    // that hash every key the same: h(b) for the first byte and h(b << 8 i) ^ h(0) for the others
+    fn recover(observe: &dyn Fn(u64) -> u64) -> Option<Self> {
+        let hashed_zero = observe(0);
+        let parameters: Vec<u8> = (0..8).flat_map(|table_idx| (0..256u64).map(move |byte| (table_idx, byte)))
+            .map(|(table_idx, byte)| observe(byte << (8 * table_idx)) ^ if table_idx == 0 { 0 } else { hashed_zero })
+            .flat_map(|entry| entry.to_le_bytes())
+            .collect();
+        SimpleTabulationHashFunction::from_parameters(&parameters)
+    }
+}
+
+// no shortcut from outputs to seeds
+impl LeakedOutputs for TwistedTabulationHashFunction {}
+impl LeakedOutputs for MurmurHash3 {}
+impl LeakedOutputs for XxHash64 {}
+impl LeakedOutputs for WyHash {}
+impl LeakedOutputs for SipHash24 {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::*;
+
+    fn max_error<H: SketchHasher>(victim: &H, recovered: &H) -> u64 {
+        random_items(1, 1000).into_iter().map(|data| {
+            let error = recovered.hash64(data).wrapping_sub(victim.hash64(data));
+            error.min(error.wrapping_neg())
+        }).max().unwrap()
+    }
+
+    #[test]
+    fn linear_families_are_recovered_from_outputs() {
+        for seed in 0..20 {
+            let victim = UniversalHashFunction::from_seed(seed);
+            let recovered = UniversalHashFunction::recover(&|data| victim.hash64(data)).unwrap();
+            assert!(max_error(&victim, &recovered) <= 4, "seed {}", seed);
+            let victim = PolynomialHashFunction::<4>::from_seed(seed);
+            assert!(PolynomialHashFunction::<4>::recover(&|data| victim.hash64(data)) == Some(victim.clone()), "seed {}", seed);
+            let victim = SimpleTabulationHashFunction::from_seed(seed);
+            assert_eq!(max_error(&victim, &SimpleTabulationHashFunction::recover(&|data| victim.hash64(data)).unwrap()), 0, "seed {}", seed);
+        }
+    }
+
+    #[test]
+    fn prfs_are_not_recovered_from_outputs() {
+        let victim = SipHash24::from_seed(1);
+        assert!(SipHash24::recover(&|data| victim.hash64(data)).is_none());
+    }
+}
@@ -0,0 +1,57 @@
+use crate::cpc::*;
+use super::{compute_relative_error, median};
+use rand::prelude::*;
+use rand::rngs::StdRng;
+
+pub fn benchmark_accuracy_cpc(master_seed: u64) {
+    let mut seeds = StdRng::seed_from_u64(master_seed);
+    for cardinality in 0u32..9 {
+        let mut relative_errors = vec![0f64;100];
+        for relative_error in relative_errors.iter_mut() {
+            let mut cpc = cpc::CPC::with_seed(11, seeds.random());
+            for data in 0..(10u64.pow(cardinality)) {
+                cpc.read_data(data);
+            }
+            let estimates = cpc.compute_estimates();
+            *relative_error = compute_relative_error(estimates, (10u64.pow(cardinality)) as f64);
+        }
+        let median_relative_error = median(&relative_errors);
+        println!("{}, ", median_relative_error);
+    }
+    println!();
+}
+
+pub fn run_benchmark_speed_cpc(master_seed: u64) {
+    use std::time::Instant;
+    let mut seeds = StdRng::seed_from_u64(master_seed);
+    let mut cpc = cpc::CPC::with_seed(11, seeds.random());
+    let mut rng = StdRng::seed_from_u64(seeds.random());
+    let time_start_1 = Instant::now();
+    for _i in 0u64..1_000_000_000u64 {
+        cpc.read_data(rng.random());
+    }
+    let time_start_1_elapsed_1 = time_start_1.elapsed();
+    println!("Finished Reading Stream and Counting in {} secs", time_start_1_elapsed_1.as_nanos() as f64 * 1e-9f64);
+    let time_start_2 = Instant::now();
+    let results = cpc.compute_estimates();
+    let time_start_2_elapsed_1 = time_start_2.elapsed();
+    println!("Finished Cardinality Estimatings in {} secs with results = {}!", time_start_2_elapsed_1.as_nanos() as f64 * 1e-9f64, results);
+}
+
+pub fn benchmark_size_cpc_hllpp(master_seed: u64) {
+    // CPC<11> and HLLPP<14, _> have roughly the same error, so compare how many bytes each one needs
+    // columns: cardinality, cpc payload bytes, hllpp payload bytes, cpc serialized bytes
+    // the payload is the registers and stored entries without the hash tables' own overhead, see size_in_bytes
+    use crate::hllpp::hllpp::HLLPP;
+    let mut seeds = StdRng::seed_from_u64(master_seed);
+    for cardinality in 0u32..9 {
+        let mut cpc = cpc::CPC::with_seed(11, seeds.random());
+        let mut hllpp = HLLPP::<14,25>::with_seed(seeds.random());
+        for data in 0..(10u64.pow(cardinality)) {
+            cpc.read_data(data);
+            hllpp.read_data(data);
+        }
+        println!("{}, {}, {}, {}, ", 10u64.pow(cardinality), cpc.size_in_bytes(), hllpp.size_in_bytes(), cpc.serialize().len());
+    }
+    println!();
+}
@@ -0,0 +1,73 @@
+use crate::cvm::*;
+use super::{compute_relative_error, median};
+use rand::prelude::*;
+use rand::rngs::StdRng;
+
+pub fn benchmark_accuracy_cvm(master_seed: u64) {
+    let mut seeds = StdRng::seed_from_u64(master_seed);
+    for cardinality in 0u32..9 {
+        let mut relative_errors = vec![0f64;100];
+        for relative_error in relative_errors.iter_mut() {
+            let mut cvm = cvm::CVM::with_seed(0.1, 0.01, 10u64.pow(8), seeds.random());
+            for data in 0..(10u64.pow(cardinality)) {
+                cvm.read_data(data);
+            }
+            let estimates = cvm.compute_estimates();
+            *relative_error = compute_relative_error(estimates, (10u64.pow(cardinality)) as f64);
+        }
+        let median_relative_error = median(&relative_errors);
+        println!("{}, ", median_relative_error);
+    }
+    println!();
+}
+
+pub fn run_benchmark_speed_cvm(master_seed: u64) {
+    use std::time::Instant;
+    let mut seeds = StdRng::seed_from_u64(master_seed);
+    let mut cvm = cvm::CVM::with_seed(0.1, 0.01, 10u64.pow(9), seeds.random());
+    let mut rng = StdRng::seed_from_u64(seeds.random());
+    let time_start_1 = Instant::now();
+    for _i in 0u64..1_000_000_000u64 {
+        cvm.read_data(rng.random());
+    }
+    let time_start_1_elapsed_1 = time_start_1.elapsed();
+    println!("Finished Reading Stream and Counting in {} secs", time_start_1_elapsed_1.as_nanos() as f64 * 1e-9f64);
+    let time_start_2 = Instant::now();
+    let results = cvm.compute_estimates();
+    let time_start_2_elapsed_1 = time_start_2.elapsed();
+    println!("Finished Cardinality Estimatings in {} secs with results = {}!", time_start_2_elapsed_1.as_nanos() as f64 * 1e-9f64, results);
+}
+
+pub fn benchmark_accuracy_cvm_structured_inputs(master_seed: u64) {
+    // sequential and strided inputs are where a linear hash like UniversalHashFunction is weakest,
+    // cvm never hashes so it should not care about the input pattern
+    use crate::hllpp::hllpp::HLLPP;
+    use crate::vanilla::hll::HLL;
+    let mut seeds = StdRng::seed_from_u64(master_seed);
+    let input_patterns = [("sequential", (|i| i) as fn(u64) -> u64), ("strided 2^32", |i| i << 32)];
+    for (input_pattern_name, input_pattern) in input_patterns {
+        println!("{}: cvm, hll, hllpp", input_pattern_name);
+        for cardinality in 0u32..7 {
+            let mut relative_errors = vec![[0f64; 3];100];
+            for relative_error in relative_errors.iter_mut() {
+                let mut cvm = cvm::CVM::with_seed(0.1, 0.01, 10u64.pow(8), seeds.random());
+                let mut hll = HLL::with_seed(14, seeds.random());
+                let mut hllpp = HLLPP::<14,25>::with_seed(seeds.random());
+                for data in (0..(10u64.pow(cardinality))).map(input_pattern) {
+                    cvm.read_data(data);
+                    hll.read_data(data);
+                    hllpp.read_data(data);
+                }
+                let true_cardinality = (10u64.pow(cardinality)) as f64;
+                *relative_error = [
+                    compute_relative_error(cvm.compute_estimates(), true_cardinality),
+                    compute_relative_error(hll.get_cardinality(), true_cardinality),
+                    compute_relative_error(hllpp.compute_estimates(), true_cardinality),
+                ];
+            }
+            let median_relative_errors: Vec<f64> = (0..3).map(|estimator_idx| median(&relative_errors.iter().map(|relative_error| relative_error[estimator_idx]).collect::<Vec<f64>>())).collect();
+            println!("{}, {}, {}, ", median_relative_errors[0], median_relative_errors[1], median_relative_errors[2]);
+        }
+        println!();
+    }
+}
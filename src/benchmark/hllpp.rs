@@ -1,13 +1,18 @@
 use crate::hllpp::*;
 use crate::vanilla;
+use crate::universalhash::hasher::SketchHasher;
+use super::{compute_relative_error, median};
+use rand::prelude::*;
+use rand::rngs::StdRng;
 
-pub fn benchmarK_accuracy_hll() {
+pub fn benchmarK_accuracy_hll(master_seed: u64) {
+    let mut seeds = StdRng::seed_from_u64(master_seed);
     for cardinality in 0u32..9 {
         // println!("c{}",cardinality);
         let mut relative_errors = vec![0f64;100];
         for run_i in 0..100 {
             // println!("r{}",run_i);
-            let mut hllpp = vanilla::hll::HLL::new(28);
+            let mut hllpp = vanilla::hll::HLL::with_seed(28, seeds.random());
             for data in 0..(10u64.pow(cardinality)) {
                 hllpp.read_data(data);
             }
@@ -21,13 +26,14 @@ pub fn benchmarK_accuracy_hll() {
     println!("");
 }
 
-pub fn benchmarK_accuracy_hllpp() {
+pub fn benchmarK_accuracy_hllpp(master_seed: u64) {
+    let mut seeds = StdRng::seed_from_u64(master_seed);
     for cardinality in 0u32..9 {
         // println!("c{}",cardinality);
         let mut relative_errors = vec![0f64;100];
         for run_i in 0..100 {
             // println!("r{}",run_i);
-            let mut hllpp = hllpp::HLLPP::<14,25>::new();
+            let mut hllpp = hllpp::HLLPP::<14,25>::with_seed(seeds.random());
             for data in 0..(10u64.pow(cardinality)) {
                 hllpp.read_data(data);
             }
@@ -41,11 +47,12 @@ pub fn benchmarK_accuracy_hllpp() {
     println!("");
 }
 
-pub fn benchmarK_accuracy_hllpprh() {
+pub fn benchmarK_accuracy_hllpprh(master_seed: u64) {
+    let mut seeds = StdRng::seed_from_u64(master_seed);
     for cardinality in 0u32..9 {
         let mut relative_errors = vec![0f64;100];
         for run_i in 0..100 {
-            let mut hllpprh = hllpprh::HLLPPRH::<14,25>::new();
+            let mut hllpprh = hllpprh::HLLPPRH::<14,25>::with_seed(seeds.random());
             for data in 0..(10u64.pow(cardinality)) {
                 hllpprh.read_data(data);
             }
@@ -59,22 +66,263 @@ pub fn benchmarK_accuracy_hllpprh() {
     println!("");
 }
 
-fn compute_relative_error(approx_val: f64, true_val: f64) -> f64 {
-    f64::abs(approx_val - true_val) / f64::abs(true_val)
+pub fn benchmark_accuracy_exaloglog(master_seed: u64) {
+    // ELL(2, 24) with 2^12 32 bit registers has the same 16KB register budget as HLLPP<14, _>
+    let mut seeds = StdRng::seed_from_u64(master_seed);
+    for cardinality in 0u32..9 {
+        let mut relative_errors = vec![0f64;100];
+        for relative_error in relative_errors.iter_mut() {
+            let mut exaloglog = exaloglog::ExaLogLog::with_seed(12, seeds.random());
+            for data in 0..(10u64.pow(cardinality)) {
+                exaloglog.read_data(data);
+            }
+            let estimates = exaloglog.compute_estimates();
+            *relative_error = compute_relative_error(estimates, (10u64.pow(cardinality)) as f64);
+        }
+        let median_relative_error = median(&relative_errors);
+        println!("{}, ", median_relative_error);
+    }
+    println!("serialized size: {} bytes", exaloglog::ExaLogLog::with_seed(12, seeds.random()).serialize().len());
+    println!();
 }
 
-fn median(vec : &Vec<f64>) -> f64 {
-    let mut count = 0;
-    let mut current_max = vec[0];
-    for &i in vec {
-        if current_max >= i {
-            count += 1;
-            current_max = i;
+pub fn benchmark_accuracy_hyperminhash_intersection(master_seed: u64) {
+    // same overlap as the kmv intersection benchmark, true intersection n/2 and true jaccard 1/3
+    // 2^10 registers of 16 bits against k = 1024 minimums of 64 bits
+    // random items instead of 0..n since the registers look at the low hash bits, which multiply-shift leaves correlated for sequential items
+    use crate::kmv::kmv::KMV;
+    use crate::universalhash::univesalhash::*;
+    let mut seeds = StdRng::seed_from_u64(master_seed);
+    let mut rng = StdRng::seed_from_u64(seeds.random());
+    for cardinality in 1u32..8 {
+        let num_data = 10u64.pow(cardinality);
+        let items: Vec<u64> = (0..(num_data / 2 + num_data)).map(|_| rng.random()).collect();
+        let mut hyperminhash_relative_errors = vec![(0f64, 0f64);100];
+        let mut kmv_relative_errors = vec![(0f64, 0f64);100];
+        for (hyperminhash_relative_error, kmv_relative_error) in hyperminhash_relative_errors.iter_mut().zip(kmv_relative_errors.iter_mut()) {
+            let hash_function = UniversalHashFunctionsFamily::with_rng(64, &mut seeds).construct_new_hash_function_with_random_seeds();
+            let mut hyperminhash_a = hyperminhash::HyperMinHash::<10, 8>::with_hash_function(hash_function.clone());
+            let mut hyperminhash_b = hyperminhash::HyperMinHash::<10, 8>::with_hash_function(hash_function.clone());
+            let mut kmv_a = KMV::with_hash_function(1024, hash_function.clone());
+            let mut kmv_b = KMV::with_hash_function(1024, hash_function);
+            for &data in &items[..num_data as usize] {
+                hyperminhash_a.read_data(data);
+                kmv_a.read_data(data);
+            }
+            for &data in &items[(num_data / 2) as usize..] {
+                hyperminhash_b.read_data(data);
+                kmv_b.read_data(data);
+            }
+            let (true_intersection, true_jaccard) = ((num_data / 2) as f64, (num_data / 2) as f64 / (num_data / 2 + num_data) as f64);
+            *hyperminhash_relative_error = (
+                compute_relative_error(hyperminhash_a.intersection_estimates(&hyperminhash_b), true_intersection),
+                compute_relative_error(hyperminhash_a.jaccard_estimates(&hyperminhash_b), true_jaccard),
+            );
+            *kmv_relative_error = (
+                compute_relative_error(kmv_a.intersection_estimates(&kmv_b), true_intersection),
+                compute_relative_error(kmv_a.jaccard_estimates(&kmv_b), true_jaccard),
+            );
         }
-        if count >= vec.len() / 2 {
-            return current_max;
+        let medians = |relative_errors: &Vec<(f64, f64)>| (
+            median(&relative_errors.iter().map(|&(intersection, _)| intersection).collect::<Vec<f64>>()),
+            median(&relative_errors.iter().map(|&(_, jaccard)| jaccard).collect::<Vec<f64>>()),
+        );
+        let (hyperminhash_intersection, hyperminhash_jaccard) = medians(&hyperminhash_relative_errors);
+        let (kmv_intersection, kmv_jaccard) = medians(&kmv_relative_errors);
+        println!("hyperminhash: {}, {}, kmv: {}, {}, ", hyperminhash_intersection, hyperminhash_jaccard, kmv_intersection, kmv_jaccard);
+    }
+    println!();
+}
+
+pub fn benchmark_size_hllpp_registry(master_seed: u64) {
+    // unique visitors per page, page popularity follows a zipf-like law over 100k pages
+    use std::collections::{HashMap, HashSet};
+    let mut seeds = StdRng::seed_from_u64(master_seed);
+    let num_pages = 100_000usize;
+    let mut rng = StdRng::seed_from_u64(seeds.random());
+    let users: Vec<u64> = (0..1_000_000).map(|_| rng.random()).collect();
+    let mut registry = registry::HLLPPRegistry::<usize, 14, 25>::with_seed(seeds.random());
+    let mut exact: HashMap<usize, HashSet<u64>> = HashMap::new();
+    for _i in 0..10_000_000 {
+        // page ~ 1 / u is a pareto tail, most visits go to a handful of pages
+        let page = ((1f64 / rng.random::<f64>()) as usize - 1).min(num_pages - 1);
+        let user = users[rng.random_range(0..users.len())];
+        registry.read_data(&page, user);
+        exact.entry(page).or_default().insert(user);
+    }
+    let mut relative_errors: Vec<f64> = exact.iter()
+        .map(|(page, page_users)| compute_relative_error(registry.compute_estimates(page), page_users.len() as f64))
+        .collect();
+    relative_errors.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    println!("{} keys, {} sparse, {} normal", registry.len(), registry.num_sparse_keys(), registry.num_normal_keys());
+    println!("median relative error = {}, 99th percentile = {}, max relative error = {}", median(&relative_errors), relative_errors[relative_errors.len() * 99 / 100], relative_errors.last().unwrap());
+    println!("registry uses {} bytes, one normal HLLPP per key would use {} bytes", registry.size_in_bytes(), registry.len() * (1 << 14));
+}
+
+pub fn benchmark_accuracy_with_hasher<H: SketchHasher>(hasher_name: &str, master_seed: u64) {
+    benchmark_accuracy_with_hasher_for::<H>(hasher_name, master_seed, true);
+}
+
+// hllpprh needs 2^14 hash functions per sketch, which is 256mb of tables per run for simple tabulation
+// and 512mb for twisted tabulation, so the table based families only run hll and hllpp
+pub fn benchmark_accuracy_with_table_hasher<H: SketchHasher>(hasher_name: &str, master_seed: u64) {
+    benchmark_accuracy_with_hasher_for::<H>(hasher_name, master_seed, false);
+}
+
+fn benchmark_accuracy_with_hasher_for<H: SketchHasher>(hasher_name: &str, master_seed: u64, with_hllpprh: bool) {
+    // same sequential inputs as the other accuracy benchmarks, the hash function is the only thing that changes
+    let mut seeds = StdRng::seed_from_u64(master_seed);
+    println!("{}: hll, hllpp{}", hasher_name, if with_hllpprh { ", hllpprh" } else { "" });
+    for cardinality in 0u32..8 {
+        let mut relative_errors = vec![(0f64, 0f64, 0f64);100];
+        for relative_error in relative_errors.iter_mut() {
+            let mut hll = vanilla::hll::HLL::with_hash_function(14, H::from_seed(seeds.random()));
+            let mut hllpp = hllpp::HLLPP::<14,25,H>::with_hash_function(H::from_seed(seeds.random()));
+            let mut hllpprh = with_hllpprh.then(|| hllpprh::HLLPPRH::<14,25,H>::with_hash_functions((0..(1 << 14)).map(|_| H::from_seed(seeds.random())).collect()));
+            for data in 0..(10u64.pow(cardinality)) {
+                hll.read_data(data);
+                hllpp.read_data(data);
+                if let Some(hllpprh) = hllpprh.as_mut() {
+                    hllpprh.read_data(data);
+                }
+            }
+            let true_cardinality = (10u64.pow(cardinality)) as f64;
+            *relative_error = (
+                compute_relative_error(hll.get_cardinality(), true_cardinality),
+                compute_relative_error(hllpp.compute_estimates(), true_cardinality),
+                hllpprh.map_or(0f64, |hllpprh| compute_relative_error(hllpprh.compute_estimates(), true_cardinality)),
+            );
+        }
+        print!(
+            "{}, {}, ",
+            median(&relative_errors.iter().map(|&(hll, _, _)| hll).collect::<Vec<f64>>()),
+            median(&relative_errors.iter().map(|&(_, hllpp, _)| hllpp).collect::<Vec<f64>>()),
+        );
+        if with_hllpprh {
+            print!("{}, ", median(&relative_errors.iter().map(|&(_, _, hllpprh)| hllpprh).collect::<Vec<f64>>()));
+        }
+        println!();
+    }
+    println!();
+}
+
+pub fn run_benchmark_speed_with_hasher<H: SketchHasher>(hasher_name: &str, master_seed: u64) {
+    use std::time::Instant;
+    let mut seeds = StdRng::seed_from_u64(master_seed);
+    let mut rng = StdRng::seed_from_u64(seeds.random());
+    let inputs: Vec<u64> = (0..100_000_000).map(|_| rng.random()).collect();
+    let mut hll = vanilla::hll::HLL::with_hash_function(14, H::from_seed(seeds.random()));
+    let mut hllpp = hllpp::HLLPP::<14,25,H>::with_hash_function(H::from_seed(seeds.random()));
+    let time_start_1 = Instant::now();
+    for &data in &inputs {
+        hll.read_data(data);
+    }
+    let time_start_1_elapsed_1 = time_start_1.elapsed();
+    let time_start_2 = Instant::now();
+    for &data in &inputs {
+        hllpp.read_data(data);
+    }
+    let time_start_2_elapsed_1 = time_start_2.elapsed();
+    println!("{}: hll read {} items in {} secs, hllpp in {} secs", hasher_name, inputs.len(), time_start_1_elapsed_1.as_nanos() as f64 * 1e-9f64, time_start_2_elapsed_1.as_nanos() as f64 * 1e-9f64);
+}
+
+pub fn benchmark_accuracy_hash_families(master_seed: u64) {
+    use crate::universalhash::univesalhash::UniversalHashFunction;
+    use crate::universalhash::tabulation::*;
+    use crate::universalhash::polynomial::PolynomialHashFunction;
+    use crate::universalhash::{murmur3::MurmurHash3, xxhash::XxHash64, wyhash::WyHash, siphash::SipHash24};
+    benchmark_accuracy_with_hasher::<UniversalHashFunction>("multiply-shift", master_seed);
+    benchmark_accuracy_with_table_hasher::<SimpleTabulationHashFunction>("simple tabulation", master_seed);
+    benchmark_accuracy_with_table_hasher::<TwistedTabulationHashFunction>("twisted tabulation", master_seed);
+    benchmark_accuracy_with_hasher::<PolynomialHashFunction<2>>("polynomial k = 2", master_seed);
+    benchmark_accuracy_with_hasher::<PolynomialHashFunction<4>>("polynomial k = 4", master_seed);
+    benchmark_accuracy_with_hasher::<MurmurHash3>("murmurhash3", master_seed);
+    benchmark_accuracy_with_hasher::<XxHash64>("xxhash64", master_seed);
+    benchmark_accuracy_with_hasher::<WyHash>("wyhash", master_seed);
+    benchmark_accuracy_with_hasher::<SipHash24>("siphash-2-4", master_seed);
+}
+
+pub fn run_benchmark_speed_hash_families(master_seed: u64) {
+    use crate::universalhash::univesalhash::UniversalHashFunction;
+    use crate::universalhash::tabulation::*;
+    use crate::universalhash::polynomial::PolynomialHashFunction;
+    use crate::universalhash::{murmur3::MurmurHash3, xxhash::XxHash64, wyhash::WyHash, siphash::SipHash24};
+    run_benchmark_speed_with_hasher::<UniversalHashFunction>("multiply-shift", master_seed);
+    run_benchmark_speed_with_hasher::<SimpleTabulationHashFunction>("simple tabulation", master_seed);
+    run_benchmark_speed_with_hasher::<TwistedTabulationHashFunction>("twisted tabulation", master_seed);
+    run_benchmark_speed_with_hasher::<PolynomialHashFunction<2>>("polynomial k = 2", master_seed);
+    run_benchmark_speed_with_hasher::<PolynomialHashFunction<4>>("polynomial k = 4", master_seed);
+    run_benchmark_speed_with_hasher::<MurmurHash3>("murmurhash3", master_seed);
+    run_benchmark_speed_with_hasher::<XxHash64>("xxhash64", master_seed);
+    run_benchmark_speed_with_hasher::<WyHash>("wyhash", master_seed);
+    run_benchmark_speed_with_hasher::<SipHash24>("siphash-2-4", master_seed);
+}
+
+pub fn benchmark_accuracy_polynomial_independence(master_seed: u64) {
+    // HLLPP error on structured inputs as the independence of the polynomial hash goes up
+    use crate::universalhash::polynomial::PolynomialHashFunction;
+    let mut seeds = StdRng::seed_from_u64(master_seed);
+    fn run<H: SketchHasher>(name: &str, seeds: &mut StdRng) {
+        let input_patterns = [("sequential", (|i| i) as fn(u64) -> u64), ("strided 2^20", |i| i << 20), ("arithmetic 1000 i + 7", |i| 1000 * i + 7)];
+        for (pattern_name, input_pattern) in input_patterns {
+            let mut relative_errors = vec![0f64;100];
+            for relative_error in relative_errors.iter_mut() {
+                let mut hllpp = hllpp::HLLPP::<14,25,H>::with_hash_function(H::from_seed(seeds.random()));
+                for i in 0..1_000_000u64 {
+                    hllpp.read_data(input_pattern(i));
+                }
+                *relative_error = compute_relative_error(hllpp.compute_estimates(), 1_000_000f64);
+            }
+            println!("{}, {}: {}, ", name, pattern_name, median(&relative_errors));
         }
     }
-    return *vec.last().unwrap();
+    run::<PolynomialHashFunction<2>>("k = 2", &mut seeds);
+    run::<PolynomialHashFunction<3>>("k = 3", &mut seeds);
+    run::<PolynomialHashFunction<4>>("k = 4", &mut seeds);
+    run::<PolynomialHashFunction<5>>("k = 5", &mut seeds);
+    run::<PolynomialHashFunction<8>>("k = 8", &mut seeds);
+    println!();
 }
 
+pub fn benchmark_accuracy_string_keys(master_seed: u64) {
+    // HLLPP on url keys that only differ in their trailing digits, the first two columns turn the string into a u64
+    // item (by xoring its 8 byte words, or by std's DefaultHasher with fixed keys) which HLLPP then hashes again,
+    // the last two use the universal string family with a multiply-shift or a twisted tabulation finalizer
+    // as the sketch's own hash function, so the key is hashed exactly once
+    use crate::universalhash::string::{StringHashFunction, StringHashFunctionsFamily};
+    use crate::universalhash::tabulation::TwistedTabulationHashFunction;
+    use std::hash::{DefaultHasher, Hash, Hasher};
+    let mut seeds = StdRng::seed_from_u64(master_seed);
+    let xor_fold = |key: &str| key.as_bytes().chunks(8).fold(0u64, |folded, chunk| {
+        let mut padded = [0u8; 8];
+        padded[..chunk.len()].copy_from_slice(chunk);
+        folded ^ u64::from_le_bytes(padded)
+    });
+    let default_hasher = |key: &str| {
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        hasher.finish()
+    };
+    println!("xor fold, default hasher, string family + multiply-shift, string family + twisted tabulation");
+    for cardinality in 1u32..7 {
+        let keys: Vec<String> = (0..10u64.pow(cardinality)).map(|i| format!("https://example.com/items/{:08}", i)).collect();
+        let mut relative_errors = vec![[0f64; 4];100];
+        for relative_error in relative_errors.iter_mut() {
+            let mut string_hash_family = StringHashFunctionsFamily::with_rng(&mut seeds);
+            let mut folded_hllpps = [(); 2].map(|_| hllpp::HLLPP::<14,25>::with_seed(seeds.random()));
+            let mut multiply_shift_hllpp = hllpp::HLLPP::<14,25,StringHashFunction>::with_hash_function(string_hash_family.construct_new_hash_function_with_random_seeds());
+            let mut tabulation_hllpp = hllpp::HLLPP::<14,25,StringHashFunction<TwistedTabulationHashFunction>>::with_hash_function(string_hash_family.construct_new_hash_function_with_finalizer(TwistedTabulationHashFunction::new(&mut seeds)));
+            for key in &keys {
+                folded_hllpps[0].read_data(xor_fold(key));
+                folded_hllpps[1].read_data(default_hasher(key));
+                multiply_shift_hllpp.read_str(key);
+                tabulation_hllpp.read_str(key);
+            }
+            *relative_error = [folded_hllpps[0].compute_estimates(), folded_hllpps[1].compute_estimates(), multiply_shift_hllpp.compute_estimates(), tabulation_hllpp.compute_estimates()]
+                .map(|estimates| compute_relative_error(estimates, keys.len() as f64));
+        }
+        let median_relative_errors: Vec<f64> = (0..4).map(|folding_idx| median(&relative_errors.iter().map(|relative_error| relative_error[folding_idx]).collect::<Vec<f64>>())).collect();
+        println!("{}, {}, {}, {}, ", median_relative_errors[0], median_relative_errors[1], median_relative_errors[2], median_relative_errors[3]);
+    }
+    println!();
+}
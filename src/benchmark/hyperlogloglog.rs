@@ -0,0 +1,21 @@
+use crate::hyperlogloglog::*;
+use rand::prelude::*;
+use rand::rngs::StdRng;
+
+pub fn run_benchmark_speed_hyperlogloglog(master_seed: u64) {
+    use std::time::Instant;
+    let mut seeds = StdRng::seed_from_u64(master_seed);
+    let mut hyperlogloglog = hyperlogloglog::HyperLogLogLog::with_seed(12, seeds.random());
+    let mut rng = StdRng::seed_from_u64(seeds.random());
+    let time_start_1 = Instant::now();
+    for _i in 0u64..1_000_000_000u64 {
+        hyperlogloglog.read_data(rng.random());
+    }
+    let time_start_1_elapsed_1 = time_start_1.elapsed();
+    println!("Finished Reading Stream and Counting in {} secs", time_start_1_elapsed_1.as_nanos() as f64 * 1e-9f64);
+    let time_start_2 = Instant::now();
+    let results = hyperlogloglog.compute_estimates();
+    let time_start_2_elapsed_1 = time_start_2.elapsed();
+    println!("Finished Cardinality Estimatings in {} secs with results = {}!", time_start_2_elapsed_1.as_nanos() as f64 * 1e-9f64, results);
+    println!("Sketch size: {} bits (6 bit hll registers: {} bits)", hyperlogloglog.size_in_bits(), 6 * (1 << 12));
+}
@@ -0,0 +1,39 @@
+use crate::linearcounting::*;
+use super::{compute_relative_error, median};
+use rand::prelude::*;
+use rand::rngs::StdRng;
+
+pub fn benchmark_accuracy_linearcounting(master_seed: u64) {
+    let mut seeds = StdRng::seed_from_u64(master_seed);
+    for cardinality in 0u32..7 {
+        let mut relative_errors = vec![0f64;100];
+        for relative_error in relative_errors.iter_mut() {
+            let mut linearcounting = linearcounting::LinearCounting::with_seed(1 << 20, seeds.random());
+            for data in 0..(10u64.pow(cardinality)) {
+                linearcounting.read_data(data);
+            }
+            let estimates = linearcounting.compute_estimates();
+            *relative_error = compute_relative_error(estimates, (10u64.pow(cardinality)) as f64);
+        }
+        let median_relative_error = median(&relative_errors);
+        println!("{}, ", median_relative_error);
+    }
+    println!();
+}
+
+pub fn run_benchmark_speed_linearcounting(master_seed: u64) {
+    use std::time::Instant;
+    let mut seeds = StdRng::seed_from_u64(master_seed);
+    let mut linearcounting = linearcounting::LinearCounting::with_seed(1 << 30, seeds.random());
+    let mut rng = StdRng::seed_from_u64(seeds.random());
+    let time_start_1 = Instant::now();
+    for _i in 0u64..1_000_000_000u64 {
+        linearcounting.read_data(rng.random());
+    }
+    let time_start_1_elapsed_1 = time_start_1.elapsed();
+    println!("Finished Reading Stream and Counting in {} secs", time_start_1_elapsed_1.as_nanos() as f64 * 1e-9f64);
+    let time_start_2 = Instant::now();
+    let results = linearcounting.compute_estimates();
+    let time_start_2_elapsed_1 = time_start_2.elapsed();
+    println!("Finished Cardinality Estimatings in {} secs with results = {}!", time_start_2_elapsed_1.as_nanos() as f64 * 1e-9f64, results);
+}
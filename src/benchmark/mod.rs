@@ -1,2 +1,44 @@
 pub mod vanilla;
-pub mod hllpp;
\ No newline at end of file
+pub mod hllpp;
+pub mod loglog;
+pub mod pcsa;
+pub mod linearcounting;
+pub mod kmv;
+pub mod ultraloglog;
+pub mod cpc;
+pub mod theta;
+pub mod hyperlogloglog;
+pub mod cvm;
+pub mod setsketch;
+pub mod rollup;
+pub mod adversarial;
+
+pub fn compute_relative_error(approx_val: f64, true_val: f64) -> f64 {
+    f64::abs(approx_val - true_val) / f64::abs(true_val)
+}
+
+pub fn median(values: &[f64]) -> f64 {
+    // the middle of the sorted values, the mean of the two middle ones for an even count
+    let mut sorted = values.to_vec();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    let middle = sorted.len() / 2;
+    if sorted.len().is_multiple_of(2) {
+        (sorted[middle - 1] + sorted[middle]) / 2f64
+    }
+    else {
+        sorted[middle]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn median_sorts_first() {
+        assert_eq!(median(&[3f64, 1f64, 2f64]), 2f64);
+        assert_eq!(median(&[4f64, 1f64, 3f64, 2f64]), 2.5);
+        assert_eq!(median(&[1f64, 2f64, 3f64, 4f64, 100f64]), 3f64);
+        assert_eq!(median(&[7f64]), 7f64);
+    }
+}
@@ -0,0 +1,39 @@
+use crate::rollup::*;
+use super::compute_relative_error;
+use rand::prelude::*;
+use rand::rngs::StdRng;
+
+pub fn benchmark_accuracy_rollup(master_seed: u64) {
+    // 3 days of traffic, every minute 200 visits from a pool of 1m users, then query ranges that hit every resolution
+    use std::collections::HashSet;
+    let mut seeds = StdRng::seed_from_u64(master_seed);
+    const MINUTE: u64 = 60;
+    const HOUR: u64 = 60 * MINUTE;
+    const DAY: u64 = 24 * HOUR;
+    let mut rng = StdRng::seed_from_u64(seeds.random());
+    // random ids rather than 0..1m, small sequential items hash badly under multiply-shift
+    let users: Vec<u64> = (0..1_000_000).map(|_| rng.random()).collect();
+    let mut rollup = rollup::TimeBucketedHLLPP::<14, 25>::with_seed(seeds.random());
+    let mut visits = vec![];
+    for minute in 0..(3 * DAY / MINUTE) {
+        for _ in 0..200 {
+            let (user, timestamp) = (users[rng.random_range(0..users.len())], minute * MINUTE + rng.random_range(0..MINUTE));
+            rollup.read_data(user, timestamp);
+            visits.push((user, timestamp));
+        }
+    }
+    let now = 3 * DAY;
+    let ranges = [
+        ("last 15 minutes", now - 15 * MINUTE, now),
+        ("last hour", now - HOUR, now),
+        ("last day", now - DAY, now),
+        ("first day", 0, DAY),
+        ("everything", 0, now),
+    ];
+    for (name, start, end) in ranges {
+        let distinct_users: HashSet<u64> = visits.iter().filter(|&&(_, timestamp)| start <= timestamp && timestamp < end).map(|&(user, _)| user).collect();
+        let estimates = rollup.compute_estimates(start, end);
+        println!("{}: {} vs {}, relative error = {}", name, estimates, distinct_users.len(), compute_relative_error(estimates, distinct_users.len() as f64));
+    }
+    println!("{} buckets using {} bytes", rollup.num_buckets(), rollup.size_in_bytes());
+}
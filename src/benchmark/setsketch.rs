@@ -0,0 +1,65 @@
+use crate::setsketch::*;
+use super::{compute_relative_error, median};
+use rand::prelude::*;
+use rand::rngs::StdRng;
+
+pub fn benchmark_accuracy_setsketch(master_seed: u64) {
+    let mut seeds = StdRng::seed_from_u64(master_seed);
+    for cardinality in 0u32..9 {
+        let mut relative_errors = vec![0f64;100];
+        for relative_error in relative_errors.iter_mut() {
+            let mut setsketch = setsketch::SetSketch::with_seed(4096, seeds.random());
+            for data in 0..(10u64.pow(cardinality)) {
+                setsketch.read_data(data);
+            }
+            let estimates = setsketch.compute_estimates();
+            *relative_error = compute_relative_error(estimates, (10u64.pow(cardinality)) as f64);
+        }
+        let median_relative_error = median(&relative_errors);
+        println!("{}, ", median_relative_error);
+    }
+    println!();
+}
+
+pub fn run_benchmark_speed_setsketch(master_seed: u64) {
+    use std::time::Instant;
+    let mut seeds = StdRng::seed_from_u64(master_seed);
+    let mut setsketch = setsketch::SetSketch::with_seed(4096, seeds.random());
+    let mut rng = StdRng::seed_from_u64(seeds.random());
+    let time_start_1 = Instant::now();
+    for _i in 0u64..1_000_000_000u64 {
+        setsketch.read_data(rng.random());
+    }
+    let time_start_1_elapsed_1 = time_start_1.elapsed();
+    println!("Finished Reading Stream and Counting in {} secs", time_start_1_elapsed_1.as_nanos() as f64 * 1e-9f64);
+    let time_start_2 = Instant::now();
+    let results = setsketch.compute_estimates();
+    let time_start_2_elapsed_1 = time_start_2.elapsed();
+    println!("Finished Cardinality Estimatings in {} secs with results = {}!", time_start_2_elapsed_1.as_nanos() as f64 * 1e-9f64, results);
+}
+
+pub fn benchmark_accuracy_setsketch_intersection(master_seed: u64) {
+    // same setup as the kmv intersection benchmark, true intersection n/2 and true jaccard 1/3
+    use crate::universalhash::univesalhash::*;
+    let mut seeds = StdRng::seed_from_u64(master_seed);
+    for cardinality in 1u32..8 {
+        let num_data = 10u64.pow(cardinality);
+        let mut intersection_relative_errors = vec![0f64;100];
+        let mut jaccard_relative_errors = vec![0f64;100];
+        for (intersection_relative_error, jaccard_relative_error) in intersection_relative_errors.iter_mut().zip(jaccard_relative_errors.iter_mut()) {
+            let hash_function = UniversalHashFunctionsFamily::with_rng(64, &mut seeds).construct_new_hash_function_with_random_seeds();
+            let mut setsketch_a = setsketch::SetSketch::with_hash_function(4096, hash_function.clone());
+            let mut setsketch_b = setsketch::SetSketch::with_hash_function(4096, hash_function);
+            for data in 0..num_data {
+                setsketch_a.read_data(data);
+            }
+            for data in (num_data / 2)..(num_data / 2 + num_data) {
+                setsketch_b.read_data(data);
+            }
+            *intersection_relative_error = compute_relative_error(setsketch_a.intersection_estimates(&setsketch_b), (num_data / 2) as f64);
+            *jaccard_relative_error = compute_relative_error(setsketch_a.jaccard_estimates(&setsketch_b), (num_data / 2) as f64 / (num_data / 2 + num_data) as f64);
+        }
+        println!("{}, {}, ", median(&intersection_relative_errors), median(&jaccard_relative_errors));
+    }
+    println!();
+}
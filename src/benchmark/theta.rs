@@ -0,0 +1,22 @@
+use crate::theta::*;
+use super::{compute_relative_error, median};
+use rand::prelude::*;
+use rand::rngs::StdRng;
+
+pub fn benchmark_accuracy_theta_sketch(master_seed: u64) {
+    let mut seeds = StdRng::seed_from_u64(master_seed);
+    for cardinality in 0u32..9 {
+        let mut relative_errors = vec![0f64;100];
+        for relative_error in relative_errors.iter_mut() {
+            let mut theta_sketch = theta::ThetaSketch::with_seed(4096, seeds.random());
+            for data in 0..(10u64.pow(cardinality)) {
+                theta_sketch.read_data(data);
+            }
+            let estimates = theta_sketch.compute_estimates();
+            *relative_error = compute_relative_error(estimates, (10u64.pow(cardinality)) as f64);
+        }
+        let median_relative_error = median(&relative_errors);
+        println!("{}, ", median_relative_error);
+    }
+    println!();
+}
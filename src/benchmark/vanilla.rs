@@ -3,13 +3,16 @@ use std::io::{self, Write};
 use rand::{rngs::ThreadRng, seq::index};
 
 use crate::vanilla::hll::*;
+use super::{compute_relative_error, median};
+use rand::prelude::*;
+use rand::rngs::StdRng;
 
-pub fn run_benchmark_speed(){
+pub fn run_benchmark_speed(master_seed: u64){
     use std::time::{Duration, Instant};
-    use rand::prelude::*;
+    let mut seeds = StdRng::seed_from_u64(master_seed);
     let num_bucket_bits = 12;
-    let mut hll = HLL::new(num_bucket_bits);
-    let mut rng = rand::rng();
+    let mut hll = HLL::with_seed(num_bucket_bits, seeds.random());
+    let mut rng = StdRng::seed_from_u64(seeds.random());
     let time_start_1 = Instant::now();
     // let input_stream = {
         
@@ -27,12 +30,12 @@ pub fn run_benchmark_speed(){
     println!("Finished Cardinality Estimatings in {} secs with results = {}!", time_start_2_elapsed_1.as_nanos() as f64 * 1e-9f64, results);
 }
 
-pub fn run_benchmark_accuracy() {
-    use rand::prelude::*;
+pub fn run_benchmark_accuracy(master_seed: u64) {
     use std::collections::HashSet;
+    let mut seeds = StdRng::seed_from_u64(master_seed);
     let num_bucket_bits = 28;
-    let mut hll = HLL::new(num_bucket_bits);
-    let mut rng = rand::rng();
+    let mut hll = HLL::with_seed(num_bucket_bits, seeds.random());
+    let mut rng = StdRng::seed_from_u64(seeds.random());
     let mut hashset = HashSet::<u64>::new();
     for _i in 0u64..1_000_000_000u64 {
         // let data = rng.random_range(1u64..1_000_000_000_000_000u64);
@@ -54,3 +57,66 @@ pub fn run_benchmark_accuracy() {
 }
 
 
+
+pub fn benchmark_accuracy_sliding_hll(master_seed: u64) {
+    // every timestamp brings 100 new random users, so a window of w timestamps holds 100 * w distinct users
+    use crate::vanilla::slidinghll::SlidingHLL;
+    let mut seeds = StdRng::seed_from_u64(master_seed);
+    let max_window = 15 * 60;
+    let windows = [1u64, 10, 60, 5 * 60, 15 * 60];
+    let mut rng = StdRng::seed_from_u64(seeds.random());
+    let mut relative_errors = vec![vec![]; windows.len()];
+    let mut num_stored_pairs = 0;
+    for _run_i in 0..20 {
+        let mut sliding_hll = SlidingHLL::with_seed(12, max_window, seeds.random());
+        for timestamp in 0..(2 * max_window) {
+            for _ in 0..100 {
+                sliding_hll.read_data(rng.random(), timestamp);
+            }
+        }
+        for (window_relative_errors, &window) in relative_errors.iter_mut().zip(windows.iter()) {
+            window_relative_errors.push(compute_relative_error(sliding_hll.compute_estimates(window), (100 * window) as f64));
+        }
+        num_stored_pairs = sliding_hll.num_stored_pairs();
+    }
+    for (window_relative_errors, window) in relative_errors.iter().zip(windows.iter()) {
+        println!("window {}: {}, ", window, median(window_relative_errors));
+    }
+    println!("stored (timestamp, rank) pairs: {}", num_stored_pairs);
+}
+
+pub fn benchmark_accuracy_vhll(master_seed: u64) {
+    // 10k flows whose sizes follow a pareto law, from very skewed (shape 1.1) to mildly skewed (shape 2.5)
+    // 2^20 shared registers and 2^8 virtual registers per flow, errors grouped by true flow size
+    use crate::vanilla::vhll::VHLL;
+    let mut seeds = StdRng::seed_from_u64(master_seed);
+    let mut rng = StdRng::seed_from_u64(seeds.random());
+    for shape in [1.1f64, 1.5, 2.5] {
+        let mut vhll = VHLL::with_seed(1 << 20, 8, seeds.random());
+        let flows: Vec<(u64, u64)> = (0..10_000)
+            .map(|_| (rng.random(), ((1f64 / rng.random::<f64>()).powf(1f64 / shape) as u64).min(1_000_000)))
+            .collect();
+        for &(flow, flow_size) in &flows {
+            for _ in 0..flow_size {
+                vhll.read_data(flow, rng.random());
+            }
+        }
+        let estimates = vhll.compute_all_estimates(&flows.iter().map(|&(flow, _)| flow).collect::<Vec<u64>>());
+        println!("pareto shape {}: {} (flow, item) pairs, estimated {}", shape, flows.iter().map(|&(_, flow_size)| flow_size).sum::<u64>(), vhll.compute_total_estimates());
+        for (min_size, max_size) in [(1u64, 10u64), (10, 100), (100, 1_000), (1_000, 10_000), (10_000, u64::MAX)] {
+            let absolute_errors: Vec<f64> = flows.iter().zip(estimates.iter())
+                .filter(|&(&(_, flow_size), _)| min_size <= flow_size && flow_size < max_size)
+                .map(|(&(_, flow_size), &estimate)| (estimate - flow_size as f64).abs())
+                .collect();
+            let relative_errors: Vec<f64> = flows.iter().zip(estimates.iter())
+                .filter(|&(&(_, flow_size), _)| min_size <= flow_size && flow_size < max_size)
+                .map(|(&(_, flow_size), &estimate)| compute_relative_error(estimate, flow_size as f64))
+                .collect();
+            if relative_errors.is_empty() {
+                continue;
+            }
+            println!("flows of size {}..{}: {} flows, median absolute error = {}, median relative error = {}", min_size, max_size, relative_errors.len(), median(&absolute_errors), median(&relative_errors));
+        }
+    }
+    println!();
+}
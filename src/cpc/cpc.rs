@@ -0,0 +1,451 @@
+use crate::universalhash::univesalhash::*;
+use rand::prelude::*;
+use std::collections::HashSet;
+use vlq::{ReadVlqExt, WriteVlqExt};
+
+// lang's compressed probabilistic counting (2017), the sketch behind apache datasketches' cpc
+// conceptually a num_rows x 64 bit matrix of "coupons" (row from the bucket bits, column from the leading zeros)
+// stored as an 8 column sliding window of bytes plus a table of surprising values:
+// zeros to the left of the window (where almost every bit is 1) and ones to the right of it (where almost every bit is 0)
+// the matrix is stored exactly, so the sketch is only lossy in the sense of the hashing
+#[derive(Clone, Copy, PartialEq)]
+enum Flavor {
+    Empty,
+    // every coupon is a surprising value
+    Sparse,
+    // the window plus the surprising values around it
+    Windowed,
+}
+
+pub struct CPC {
+    num_row_bits: usize,
+    num_coupons: usize,
+    window_offset: usize,
+    window: Vec<u8>,
+    surprising_values: HashSet<u32>,
+    // hip (historic inverse probability) state, only valid if the sketch was never merged
+    kxp: f64,
+    hip_estimates: f64,
+    is_merged: bool,
+    hasher: UniversalHashFunction,
+    seed: Option<u64>,
+}
+
+impl CPC {
+    const NUM_COLUMNS: usize = 64;
+    const WINDOW_NUM_COLUMNS: usize = 8;
+
+    pub fn new(num_row_bits: usize) -> Self {
+        Self::with_seed(num_row_bits, rand::rng().random())
+    }
+
+    pub fn with_seed(num_row_bits: usize, seed: u64) -> Self {
+        let mut cpc = Self::with_hash_function(num_row_bits, UniversalHashFunctionsFamily::with_seed(64, seed).construct_new_hash_function_with_random_seeds());
+        cpc.seed = Some(seed);
+        cpc
+    }
+
+    pub fn with_hash_function(num_row_bits: usize, hasher: UniversalHashFunction) -> Self {
+        assert!((4..=26).contains(&num_row_bits), "cpc supports 4 to 26 row bits");
+        Self {
+            num_row_bits,
+            num_coupons: 0,
+            window_offset: 0,
+            window: vec![],
+            surprising_values: HashSet::new(),
+            kxp: (1 << num_row_bits) as f64,
+            hip_estimates: 0f64,
+            is_merged: false,
+            hasher,
+            seed: None,
+        }
+    }
+
+    pub fn seed(&self) -> Option<u64> {
+        self.seed
+    }
+
+    pub fn read_data(&mut self, data: u64) {
+        let hashed_data = self.hasher.hash64(data);
+        let row = (hashed_data >> (64 - self.num_row_bits)) as usize;
+        let col = (((hashed_data << self.num_row_bits) | ((1 << self.num_row_bits) - 1)).leading_zeros() as usize).min(Self::NUM_COLUMNS - 1);
+        if self.set_coupon(row, col) && !self.is_merged {
+            self.hip_estimates += self.num_rows() as f64 / self.kxp;
+            self.kxp -= 2f64.powi(-(col as i32 + 1));
+        }
+    }
+
+    pub fn compute_estimates(&self) -> f64 {
+        // hip is more accurate but it is a property of the stream, a merged sketch only has its coupons left
+        if self.is_merged {
+            self.compute_icon_estimates()
+        }
+        else {
+            self.compute_hip_estimates()
+        }
+    }
+
+    pub fn compute_hip_estimates(&self) -> f64 {
+        self.hip_estimates
+    }
+
+    pub fn compute_icon_estimates(&self) -> f64 {
+        // icon: the cardinality whose expected number of coupons is the observed number of coupons
+        // E[coupons | n] = num_rows * sum_col (1 - exp(-n * 2^-(col + 1) / num_rows))
+        if self.num_coupons == 0 {
+            return 0f64;
+        }
+        let num_rows = self.num_rows() as f64;
+        let expected_num_coupons = |cardinality: f64| -> f64 {
+            (0..Self::NUM_COLUMNS)
+                .map(|col| -f64::exp_m1(-cardinality * 2f64.powi(-(col as i32 + 1)) / num_rows))
+                .sum::<f64>() * num_rows
+        };
+        let num_coupons = self.num_coupons as f64;
+        let (mut low, mut high) = (-5f64, 60f64);
+        for _i in 0..200 {
+            let mid = (low + high) / 2f64;
+            if expected_num_coupons(mid.exp()) < num_coupons {
+                low = mid;
+            }
+            else {
+                high = mid;
+            }
+        }
+        ((low + high) / 2f64).exp()
+    }
+
+    pub fn merge(&mut self, other: &Self) {
+        assert_eq!(self.num_row_bits, other.num_row_bits, "cannot merge cpcs with different number of rows");
+        assert!(self.hasher == other.hasher, "cannot merge cpcs with different hash functions");
+        self.is_merged = true;
+        for (row, col) in other.coupons() {
+            self.set_coupon(row, col);
+        }
+    }
+
+    // payload bytes, counted like HLLPP::size_in_bytes: the window bytes and 4 bytes per surprising value
+    // the hash set allocates more than that (spare capacity and a control byte per slot), which this leaves out
+    pub fn size_in_bytes(&self) -> usize {
+        self.window.len() + self.surprising_values.len() * std::mem::size_of::<u32>()
+    }
+
+    pub fn serialize(&self) -> Vec<u8> {
+        // header, then the surprising values sorted and delta coded as vlqs,
+        // then every window column as whichever is smallest of: the rows that are 1, the rows that are 0, or a raw bitmap
+        let mut bytes = vec![self.num_row_bits as u8, self.is_merged as u8];
+        bytes.write_vlq(self.num_coupons as u64).unwrap();
+        bytes.write_vlq(self.window_offset as u64).unwrap();
+        bytes.extend_from_slice(&self.hip_estimates.to_le_bytes());
+        bytes.extend_from_slice(&self.kxp.to_le_bytes());
+        let mut surprising_values: Vec<u32> = self.surprising_values.iter().copied().collect();
+        surprising_values.sort_unstable();
+        Self::write_delta_coded(&mut bytes, &surprising_values);
+        if !self.window.is_empty() {
+            for window_col in 0..Self::WINDOW_NUM_COLUMNS {
+                Self::write_window_column(&mut bytes, &self.window, window_col);
+            }
+        }
+        bytes
+    }
+
+    pub fn deserialize(bytes: &[u8], hasher: UniversalHashFunction) -> Option<Self> {
+        // the hash function is not part of the serialized sketch, the caller has to bring the same one
+        let mut reader = bytes;
+        let header: [u8; 2] = reader.get(..2)?.try_into().ok()?;
+        reader = &reader[2..];
+        if !(4..=26).contains(&header[0]) || header[1] > 1 {
+            return None;
+        }
+        let mut cpc = Self::with_hash_function(header[0] as usize, hasher);
+        cpc.is_merged = header[1] == 1;
+        cpc.num_coupons = ReadVlqExt::<u64>::read_vlq(&mut reader).ok()? as usize;
+        // the matrix has at most num_rows x 64 coupons, which also keeps the flavor and window offset from overflowing
+        if cpc.num_coupons > cpc.num_rows() * Self::NUM_COLUMNS {
+            return None;
+        }
+        cpc.window_offset = ReadVlqExt::<u64>::read_vlq(&mut reader).ok()? as usize;
+        cpc.hip_estimates = f64::from_le_bytes(reader.get(..8)?.try_into().ok()?);
+        cpc.kxp = f64::from_le_bytes(reader.get(8..16)?.try_into().ok()?);
+        reader = &reader[16..];
+        cpc.surprising_values = Self::read_delta_coded(&mut reader)?.into_iter().collect();
+        if cpc.flavor() == Flavor::Windowed {
+            cpc.window = vec![0; cpc.num_rows()];
+            for window_col in 0..Self::WINDOW_NUM_COLUMNS {
+                Self::read_window_column(&mut reader, &mut cpc.window, window_col)?;
+            }
+        }
+        // the window never slides past the last column for any cardinality a 64 bit hash reaches
+        if !reader.is_empty() || cpc.window_offset != Self::compute_window_offset(cpc.num_rows(), cpc.num_coupons)
+            || cpc.window_offset + Self::WINDOW_NUM_COLUMNS > Self::NUM_COLUMNS {
+            return None;
+        }
+        if !cpc.surprising_values.iter().all(|&key| cpc.is_valid_surprising_value(key)) || cpc.coupons().len() != cpc.num_coupons {
+            return None;
+        }
+        Some(cpc)
+    }
+
+    // a coupon of the matrix, and outside of the window once there is one
+    fn is_valid_surprising_value(&self, key: u32) -> bool {
+        let (row, col) = ((key >> 6) as usize, (key & 63) as usize);
+        row < self.num_rows() && (self.window.is_empty() || col < self.window_offset || col >= self.window_offset + Self::WINDOW_NUM_COLUMNS)
+    }
+
+    fn num_rows(&self) -> usize {
+        1 << self.num_row_bits
+    }
+
+    fn flavor(&self) -> Flavor {
+        let (num_coupons, num_rows) = (self.num_coupons, self.num_rows());
+        if num_coupons == 0 {
+            Flavor::Empty
+        }
+        else if 32 * num_coupons < 3 * num_rows {
+            Flavor::Sparse
+        }
+        else {
+            Flavor::Windowed
+        }
+    }
+
+    fn compute_window_offset(num_rows: usize, num_coupons: usize) -> usize {
+        // keep the window centered on the columns that are roughly half full
+        if 8 * num_coupons < 19 * num_rows {
+            0
+        }
+        else {
+            (8 * num_coupons - 19 * num_rows) / (8 * num_rows)
+        }
+    }
+
+    fn get_key(row: usize, col: usize) -> u32 {
+        ((row << 6) | col) as u32
+    }
+
+    fn set_coupon(&mut self, row: usize, col: usize) -> bool {
+        let is_new_coupon = if self.window.is_empty() {
+            self.surprising_values.insert(Self::get_key(row, col))
+        }
+        else if col < self.window_offset {
+            self.surprising_values.remove(&Self::get_key(row, col))
+        }
+        else if col < self.window_offset + Self::WINDOW_NUM_COLUMNS {
+            let bit = 1 << (col - self.window_offset);
+            let is_new = self.window[row] & bit == 0;
+            self.window[row] |= bit;
+            is_new
+        }
+        else {
+            self.surprising_values.insert(Self::get_key(row, col))
+        };
+        if is_new_coupon {
+            self.num_coupons += 1;
+            if self.window.is_empty() && self.flavor() == Flavor::Windowed {
+                self.promote_to_window();
+            }
+            while self.window_offset < Self::compute_window_offset(self.num_rows(), self.num_coupons) {
+                self.slide_window();
+            }
+        }
+        is_new_coupon
+    }
+
+    fn promote_to_window(&mut self) {
+        self.window = vec![0; self.num_rows()];
+        let window = &mut self.window;
+        self.surprising_values.retain(|&key| {
+            let (row, col) = ((key >> 6) as usize, (key & 63) as usize);
+            if col < Self::WINDOW_NUM_COLUMNS {
+                window[row] |= 1 << col;
+                false
+            }
+            else {
+                true
+            }
+        });
+    }
+
+    fn slide_window(&mut self) {
+        // the leftmost window column joins the mostly-1 zone so its zeros become surprising,
+        // and the column right after the window leaves the mostly-0 zone so its ones move into the window
+        let (leaving_col, entering_col) = (self.window_offset, self.window_offset + Self::WINDOW_NUM_COLUMNS);
+        for (row, window_byte) in self.window.iter_mut().enumerate() {
+            if *window_byte & 1 == 0 {
+                self.surprising_values.insert(Self::get_key(row, leaving_col));
+            }
+            *window_byte >>= 1;
+            if entering_col < Self::NUM_COLUMNS && self.surprising_values.remove(&Self::get_key(row, entering_col)) {
+                *window_byte |= 1 << (Self::WINDOW_NUM_COLUMNS - 1);
+            }
+        }
+        self.window_offset += 1;
+    }
+
+    fn coupons(&self) -> Vec<(usize, usize)> {
+        let mut coupons = Vec::with_capacity(self.num_coupons);
+        if self.window.is_empty() {
+            coupons.extend(self.surprising_values.iter().map(|&key| ((key >> 6) as usize, (key & 63) as usize)));
+            return coupons;
+        }
+        for (row, &window_byte) in self.window.iter().enumerate() {
+            for col in 0..self.window_offset {
+                if !self.surprising_values.contains(&Self::get_key(row, col)) {
+                    coupons.push((row, col));
+                }
+            }
+            for window_col in 0..Self::WINDOW_NUM_COLUMNS {
+                if window_byte & (1 << window_col) != 0 {
+                    coupons.push((row, self.window_offset + window_col));
+                }
+            }
+        }
+        coupons.extend(self.surprising_values.iter()
+            .map(|&key| ((key >> 6) as usize, (key & 63) as usize))
+            .filter(|&(_row, col)| col >= self.window_offset + Self::WINDOW_NUM_COLUMNS));
+        coupons
+    }
+
+    fn write_delta_coded(bytes: &mut Vec<u8>, sorted_values: &[u32]) {
+        bytes.write_vlq(sorted_values.len() as u64).unwrap();
+        let mut prev_value = 0;
+        for &value in sorted_values {
+            bytes.write_vlq(value - prev_value).unwrap();
+            prev_value = value;
+        }
+    }
+
+    fn read_delta_coded(reader: &mut &[u8]) -> Option<Vec<u32>> {
+        let num_values = ReadVlqExt::<u64>::read_vlq(reader).ok()? as usize;
+        let mut values = Vec::with_capacity(num_values.min(reader.len()));
+        let mut prev_value = 0u32;
+        for _i in 0..num_values {
+            prev_value = prev_value.checked_add(ReadVlqExt::<u32>::read_vlq(reader).ok()?)?;
+            values.push(prev_value);
+        }
+        Some(values)
+    }
+
+    fn write_window_column(bytes: &mut Vec<u8>, window: &[u8], window_col: usize) {
+        const ONES: u8 = 0;
+        const ZEROS: u8 = 1;
+        const RAW: u8 = 2;
+        let column: Vec<bool> = window.iter().map(|&window_byte| window_byte & (1 << window_col) != 0).collect();
+        let rows_with = |bit: bool| -> Vec<u32> {
+            column.iter().enumerate().filter(|(_row, &is_set)| is_set == bit).map(|(row, _is_set)| row as u32).collect()
+        };
+        let mut ones = vec![ONES];
+        Self::write_delta_coded(&mut ones, &rows_with(true));
+        let mut zeros = vec![ZEROS];
+        Self::write_delta_coded(&mut zeros, &rows_with(false));
+        let mut raw = vec![RAW];
+        for chunk in column.chunks(8) {
+            raw.push(chunk.iter().enumerate().fold(0u8, |byte, (bit_idx, &is_set)| byte | ((is_set as u8) << bit_idx)));
+        }
+        bytes.extend_from_slice([ones, zeros, raw].iter().min_by_key(|encoded| encoded.len()).unwrap());
+    }
+
+    fn read_window_column(reader: &mut &[u8], window: &mut [u8], window_col: usize) -> Option<()> {
+        let (&mode, rest) = reader.split_first()?;
+        *reader = rest;
+        match mode {
+            0 | 1 => {
+                if mode == 1 {
+                    window.iter_mut().for_each(|window_byte| *window_byte |= 1 << window_col);
+                }
+                for row in Self::read_delta_coded(reader)? {
+                    *window.get_mut(row as usize)? ^= 1 << window_col;
+                }
+            },
+            2 => {
+                let raw = reader.get(..window.len().div_ceil(8))?;
+                for (row, window_byte) in window.iter_mut().enumerate() {
+                    *window_byte |= ((raw[row / 8] >> (row % 8)) & 1) << window_col;
+                }
+                *reader = &reader[raw.len()..];
+            },
+            _ => return None,
+        }
+        Some(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::*;
+
+    fn cpc_of(items: &[u64]) -> CPC {
+        let mut cpc = CPC::with_hash_function(10, hash_function(1));
+        for &data in items {
+            cpc.read_data(data);
+        }
+        cpc
+    }
+
+    fn sorted_coupons(cpc: &CPC) -> Vec<(usize, usize)> {
+        let mut coupons = cpc.coupons();
+        coupons.sort_unstable();
+        coupons
+    }
+
+    #[test]
+    fn estimates_are_accurate() {
+        // standard error is about 0.6 / sqrt(2^10) = 1.9% for hip and 0.7 / sqrt(2^10) = 2.2% for icon
+        // 20 items are sparse, the rest have a window that has slid a few times by 1m
+        for num_items in [20, 5000, 1_000_000] {
+            let cpc = cpc_of(&random_items(2, num_items));
+            for estimates in [cpc.compute_hip_estimates(), cpc.compute_icon_estimates()] {
+                assert!((estimates - num_items as f64).abs() < 0.1 * num_items as f64, "{} items, estimate {}", num_items, estimates);
+            }
+        }
+    }
+
+    #[test]
+    fn merge_equals_inserting_everything() {
+        // the small sketch is still sparse when it gets merged into the big one
+        let (a_items, b_items) = (random_items(3, 50_000), random_items(4, 50));
+        let mut merged = cpc_of(&a_items);
+        merged.merge(&cpc_of(&b_items));
+        let single = cpc_of(&[a_items, b_items].concat());
+        assert_eq!(sorted_coupons(&merged), sorted_coupons(&single));
+        assert_eq!(merged.compute_estimates(), single.compute_icon_estimates());
+    }
+
+    #[test]
+    fn serialize_round_trips() {
+        for num_items in [0, 20, 5000, 1_000_000] {
+            let cpc = cpc_of(&random_items(5, num_items));
+            let bytes = cpc.serialize();
+            let restored = CPC::deserialize(&bytes, hash_function(1)).unwrap();
+            assert_eq!(sorted_coupons(&restored), sorted_coupons(&cpc), "{} items", num_items);
+            assert_eq!(restored.compute_estimates(), cpc.compute_estimates());
+            assert!(CPC::deserialize(&bytes[..bytes.len() - 1], hash_function(1)).is_none());
+        }
+    }
+
+    #[test]
+    fn deserialize_rejects_corrupt_buffers() {
+        for num_items in [20, 5000] {
+            let cpc = cpc_of(&random_items(6, num_items));
+            let corrupt = |corrupt_cpc: fn(&mut CPC)| {
+                let mut corrupted = cpc_of(&random_items(6, num_items));
+                corrupt_cpc(&mut corrupted);
+                CPC::deserialize(&corrupted.serialize(), hash_function(1))
+            };
+            assert!(CPC::deserialize(&cpc.serialize(), hash_function(1)).is_some());
+            // more coupons than the matrix holds used to overflow the flavor thresholds
+            assert!(corrupt(|cpc| cpc.num_coupons = usize::MAX).is_none());
+            assert!(corrupt(|cpc| cpc.num_coupons += 1).is_none());
+            // a row past the matrix, with the count still adding up
+            assert!(corrupt(|cpc| {
+                cpc.surprising_values.insert(CPC::get_key(1 << 10, 63));
+                cpc.num_coupons += 1;
+            }).is_none());
+        }
+        // a surprising value inside the window
+        let mut cpc = cpc_of(&random_items(7, 5000));
+        cpc.surprising_values.insert(CPC::get_key(0, cpc.window_offset));
+        assert!(CPC::deserialize(&cpc.serialize(), hash_function(1)).is_none());
+    }
+}
@@ -0,0 +1,108 @@
+use rand::prelude::*;
+use rand::rngs::StdRng;
+use std::collections::HashSet;
+
+// chakraborty, vinodchandran & meel's distinct elements sampler (2023)
+// keeps a random sample of the distinct items seen so far, every item is in the sample with probability p,
+// and halves p (by flipping a coin for every sampled item) whenever the buffer fills up
+// the estimate is |sample| / p, with an (epsilon, delta) guarantee that does not depend on any hash function
+pub struct CVM {
+    threshold: usize,
+    sampling_probability: f64,
+    buffer: HashSet<u64>,
+    rng: StdRng,
+    seed: Option<u64>,
+}
+
+impl CVM {
+    pub fn new(epsilon: f64, delta: f64, max_stream_len: u64) -> Self {
+        Self::with_seed(epsilon, delta, max_stream_len, rand::rng().random())
+    }
+
+    // the coin flips are the only randomness, so the seed fixes the whole run
+    pub fn with_seed(epsilon: f64, delta: f64, max_stream_len: u64, seed: u64) -> Self {
+        // threshold from the paper: ceil(12 / epsilon^2 * log2(8 * max_stream_len / delta))
+        assert!(epsilon > 0f64 && epsilon < 1f64, "cvm needs epsilon in (0, 1)");
+        assert!(delta > 0f64 && delta < 1f64, "cvm needs delta in (0, 1)");
+        let threshold = (12f64 / (epsilon * epsilon) * f64::log2(8f64 * max_stream_len.max(1) as f64 / delta)).ceil() as usize;
+        Self::with_threshold_and_seed(threshold, seed)
+    }
+
+    pub fn with_threshold(threshold: usize) -> Self {
+        Self::with_threshold_and_seed(threshold, rand::rng().random())
+    }
+
+    pub fn with_threshold_and_seed(threshold: usize, seed: u64) -> Self {
+        assert!(threshold > 0, "cvm needs a threshold > 0");
+        Self {
+            threshold,
+            sampling_probability: 1f64,
+            buffer: HashSet::with_capacity(threshold),
+            rng: StdRng::seed_from_u64(seed),
+            seed: Some(seed),
+        }
+    }
+
+    pub fn seed(&self) -> Option<u64> {
+        self.seed
+    }
+
+    pub fn read_data(&mut self, data: u64) {
+        self.buffer.remove(&data);
+        if self.rng.random_bool(self.sampling_probability) {
+            self.buffer.insert(data);
+        }
+        // the paper fails (outputs bottom) if a single halving does not make room, which happens with probability <= delta / 8
+        // instead of failing we keep halving, which only matters in that unlikely case
+        while self.buffer.len() >= self.threshold {
+            let rng = &mut self.rng;
+            self.buffer.retain(|_data| rng.random_bool(0.5));
+            self.sampling_probability /= 2f64;
+        }
+    }
+
+    pub fn compute_estimates(&self) -> f64 {
+        self.buffer.len() as f64 / self.sampling_probability
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::*;
+
+    #[test]
+    fn exact_below_threshold() {
+        // p stays 1 until the buffer fills up, so the sample is the whole set
+        let mut cvm = CVM::with_threshold_and_seed(1000, 1);
+        for data in (0..999u64).chain(0..999u64) {
+            cvm.read_data(data);
+        }
+        assert_eq!(cvm.compute_estimates(), 999f64);
+    }
+
+    #[test]
+    fn estimates_are_within_epsilon() {
+        // no hashing, so sequential and strided inputs are as good as random ones
+        let input_patterns = [("sequential", (|i| i) as fn(u64) -> u64), ("strided 2^32", |i| i << 32)];
+        for (input_pattern_name, input_pattern) in input_patterns {
+            let mut cvm = CVM::with_seed(0.1, 0.01, 10u64.pow(6), 2);
+            for data in (0..10u64.pow(6)).map(input_pattern) {
+                cvm.read_data(data);
+            }
+            assert!(cvm.sampling_probability < 1f64, "{} inputs never filled the buffer", input_pattern_name);
+            assert!((cvm.compute_estimates() - 1e6).abs() < 0.1 * 1e6, "{} inputs: estimate {}", input_pattern_name, cvm.compute_estimates());
+        }
+    }
+
+    #[test]
+    fn repeated_items_are_counted_once() {
+        // every item 3 times, the repeats are dropped and sampled again with the current p
+        let items = random_items(1, 200_000);
+        let mut cvm = CVM::with_seed(0.1, 0.01, 10u64.pow(6), 2);
+        for data in items.iter().chain(&items).chain(&items) {
+            cvm.read_data(*data);
+        }
+        assert!((cvm.compute_estimates() - 200_000f64).abs() < 0.1 * 200_000f64, "estimate {}", cvm.compute_estimates());
+    }
+}
@@ -0,0 +1,292 @@
+use crate::universalhash::univesalhash::*;
+use rand::prelude::*;
+
+// ertl's exaloglog (2024)
+// like ultraloglog every register keeps the max update value plus a history of the update values below it,
+// but the update values have t extra hash bits folded in (so they follow a geometric distribution with base 2^(2^-t))
+// and the history is d bits long, which lets the ml estimator get ~43% below hll's memory-variance product
+// registers are stored as (max update value << d) | history, where history bit d - i means "update value max - i was seen"
+pub struct ExaLogLog {
+    num_bucket_bits: usize,
+    num_extra_bits: usize,
+    num_history_bits: usize,
+    registers: Vec<u32>,
+    hash_function: UniversalHashFunction,
+    seed: Option<u64>,
+}
+
+impl ExaLogLog {
+    // ELL(2, 24) from the paper, 32 bit registers
+    const DEFAULT_NUM_EXTRA_BITS: usize = 2;
+    const DEFAULT_NUM_HISTORY_BITS: usize = 24;
+    const SERIALIZATION_HEADER_LEN: usize = 3;
+
+    pub fn new(num_bucket_bits: usize) -> Self {
+        Self::with_seed(num_bucket_bits, rand::rng().random())
+    }
+
+    pub fn with_seed(num_bucket_bits: usize, seed: u64) -> Self {
+        let mut ell = Self::with_parameters(
+            num_bucket_bits,
+            Self::DEFAULT_NUM_EXTRA_BITS,
+            Self::DEFAULT_NUM_HISTORY_BITS,
+            UniversalHashFunctionsFamily::with_seed(64, seed).construct_new_hash_function_with_random_seeds(),
+        );
+        ell.seed = Some(seed);
+        ell
+    }
+
+    pub fn with_parameters(num_bucket_bits: usize, num_extra_bits: usize, num_history_bits: usize, hash_function: UniversalHashFunction) -> Self {
+        assert!((2..=26).contains(&num_bucket_bits), "exaloglog supports 2 to 26 bucket bits");
+        assert!(num_extra_bits <= 2, "exaloglog supports at most 2 extra bits");
+        assert!(6 + num_extra_bits + num_history_bits <= 32, "exaloglog registers must fit in 32 bits");
+        Self {
+            num_bucket_bits,
+            num_extra_bits,
+            num_history_bits,
+            registers: vec![0; 1 << num_bucket_bits],
+            hash_function,
+            seed: None,
+        }
+    }
+
+    pub fn seed(&self) -> Option<u64> {
+        self.seed
+    }
+
+    pub fn read_data(&mut self, data: u64) {
+        let hashed_data = self.hash_function.hash64(data);
+        let bucket_idx = (hashed_data >> (64 - self.num_bucket_bits)) as usize;
+        let update_value = self.get_update_value(hashed_data);
+        self.registers[bucket_idx] = Self::combine(self.registers[bucket_idx], update_value << self.num_history_bits, self.num_history_bits);
+    }
+
+    pub fn compute_estimates(&self) -> f64 {
+        // poisson model: update value k arrives at rate lambda * rho(k) in every register
+        // log likelihood = -lambda * alpha + sum_k beta[k] * ln(1 - exp(-lambda * rho(k)))
+        let rates = self.compute_update_value_rates();
+        let mut tail_rates = vec![0f64; rates.len() + 1];
+        for update_value in (1..rates.len()).rev() {
+            tail_rates[update_value - 1] = tail_rates[update_value] + rates[update_value];
+        }
+        let mut alpha = 0f64;
+        let mut beta = vec![0usize; rates.len()];
+        for &register in &self.registers {
+            let max_update_value = (register >> self.num_history_bits) as usize;
+            if max_update_value == 0 {
+                alpha += 1f64;
+                continue;
+            }
+            beta[max_update_value] += 1;
+            alpha += tail_rates[max_update_value];
+            for history_idx in 1..=self.num_history_bits.min(max_update_value - 1) {
+                let update_value = max_update_value - history_idx;
+                if (register >> (self.num_history_bits - history_idx)) & 1 == 1 {
+                    beta[update_value] += 1;
+                }
+                else {
+                    alpha += rates[update_value];
+                }
+            }
+        }
+        if beta.iter().all(|&count| count == 0) {
+            return 0f64;
+        }
+        if alpha == 0f64 {
+            return f64::INFINITY;
+        }
+        // the derivative of the log likelihood is strictly decreasing in lambda, so bisect on ln(lambda)
+        let derivative = |lambda: f64| -> f64 {
+            beta.iter().zip(rates.iter())
+                .filter(|(&count, _rate)| count > 0)
+                .map(|(&count, &rate)| count as f64 * rate / f64::exp_m1(lambda * rate))
+                .sum::<f64>() - alpha
+        };
+        let (mut low, mut high) = (-50f64, 50f64);
+        for _i in 0..200 {
+            let mid = (low + high) / 2f64;
+            if derivative(mid.exp()) > 0f64 {
+                low = mid;
+            }
+            else {
+                high = mid;
+            }
+        }
+        self.registers.len() as f64 * ((low + high) / 2f64).exp()
+    }
+
+    pub fn merge(&mut self, other: &Self) {
+        assert!(
+            (self.num_bucket_bits, self.num_extra_bits, self.num_history_bits) == (other.num_bucket_bits, other.num_extra_bits, other.num_history_bits),
+            "cannot merge exaloglogs with different parameters"
+        );
+        assert!(self.hash_function == other.hash_function, "cannot merge exaloglogs with different hash functions");
+        for (register, &other_register) in self.registers.iter_mut().zip(other.registers.iter()) {
+            *register = Self::combine(*register, other_register, self.num_history_bits);
+        }
+    }
+
+    pub fn serialize(&self) -> Vec<u8> {
+        // header (bucket bits, extra bits, history bits) followed by the registers bit-packed at 6 + t + d bits each
+        let register_num_bits = self.register_num_bits();
+        let mut bytes = vec![self.num_bucket_bits as u8, self.num_extra_bits as u8, self.num_history_bits as u8];
+        bytes.reserve_exact((self.registers.len() * register_num_bits).div_ceil(8));
+        let (mut buffer, mut buffer_num_bits) = (0u64, 0usize);
+        for &register in &self.registers {
+            buffer |= (register as u64) << buffer_num_bits;
+            buffer_num_bits += register_num_bits;
+            while buffer_num_bits >= 8 {
+                bytes.push(buffer as u8);
+                buffer >>= 8;
+                buffer_num_bits -= 8;
+            }
+        }
+        if buffer_num_bits > 0 {
+            bytes.push(buffer as u8);
+        }
+        bytes
+    }
+
+    pub fn deserialize(bytes: &[u8], hash_function: UniversalHashFunction) -> Option<Self> {
+        // the hash function is not part of the serialized sketch, the caller has to bring the same one
+        let header = bytes.get(..Self::SERIALIZATION_HEADER_LEN)?;
+        let (num_bucket_bits, num_extra_bits, num_history_bits) = (header[0] as usize, header[1] as usize, header[2] as usize);
+        if !(2..=26).contains(&num_bucket_bits) || num_extra_bits > 2 || 6 + num_extra_bits + num_history_bits > 32 {
+            return None;
+        }
+        let mut exaloglog = Self::with_parameters(num_bucket_bits, num_extra_bits, num_history_bits, hash_function);
+        let register_num_bits = exaloglog.register_num_bits();
+        let body = &bytes[Self::SERIALIZATION_HEADER_LEN..];
+        if body.len() != (exaloglog.registers.len() * register_num_bits).div_ceil(8) {
+            return None;
+        }
+        let mut body_bytes = body.iter();
+        let (mut buffer, mut buffer_num_bits) = (0u64, 0usize);
+        for register in exaloglog.registers.iter_mut() {
+            while buffer_num_bits < register_num_bits {
+                buffer |= (*body_bytes.next()? as u64) << buffer_num_bits;
+                buffer_num_bits += 8;
+            }
+            *register = (buffer & ((1 << register_num_bits) - 1)) as u32;
+            buffer >>= register_num_bits;
+            buffer_num_bits -= register_num_bits;
+        }
+        // a corrupt register would index past the rates when estimating
+        let max_update_value = exaloglog.compute_update_value_rates().len() - 1;
+        if !exaloglog.registers.iter().all(|&register| exaloglog.is_valid_register(register, max_update_value)) {
+            return None;
+        }
+        Some(exaloglog)
+    }
+
+    // the update value is at most max_update_value and the history only has bits for update values >= 1
+    // (an empty register has no history at all)
+    fn is_valid_register(&self, register: u32, max_update_value: usize) -> bool {
+        let register_max_update_value = (register >> self.num_history_bits) as usize;
+        if register_max_update_value > max_update_value {
+            return false;
+        }
+        if register_max_update_value > self.num_history_bits {
+            return true;
+        }
+        let num_invalid_history_bits = self.num_history_bits - register_max_update_value + 1;
+        register & ((1 << num_invalid_history_bits) - 1) == 0
+    }
+
+    fn register_num_bits(&self) -> usize {
+        6 + self.num_extra_bits + self.num_history_bits
+    }
+
+    fn get_update_value(&self, hashed_data: u64) -> u32 {
+        // t bits right after the bucket bits, then the leading zeros of whatever is left (capped)
+        // update value = leading_zeros * 2^t + extra_bits + 1
+        let num_prefix_bits = self.num_bucket_bits + self.num_extra_bits;
+        let extra_bits = (hashed_data >> (64 - num_prefix_bits)) & ((1 << self.num_extra_bits) - 1);
+        let num_leading_zeros = ((hashed_data << num_prefix_bits) | ((1 << num_prefix_bits) - 1)).leading_zeros() as u64;
+        ((num_leading_zeros << self.num_extra_bits) + extra_bits + 1) as u32
+    }
+
+    fn compute_update_value_rates(&self) -> Vec<f64> {
+        // rates[k] = probability that a hash has update value k (rates[0] is unused)
+        // every leading zeros count covers 2^t update values, the last count also takes the capped tail
+        let max_num_leading_zeros = 64 - self.num_bucket_bits - self.num_extra_bits;
+        let mut rates = vec![0f64];
+        for num_leading_zeros in 0..=max_num_leading_zeros {
+            let num_leading_zeros_rate = 2f64.powi(-((num_leading_zeros + 1).min(max_num_leading_zeros) as i32));
+            for _extra_bits in 0..(1 << self.num_extra_bits) {
+                rates.push(num_leading_zeros_rate / (1 << self.num_extra_bits) as f64);
+            }
+        }
+        rates
+    }
+
+    fn combine(register: u32, other_register: u32, num_history_bits: usize) -> u32 {
+        // max of the two update values, with both histories aligned below it
+        // an update value is represented by a register with an empty history
+        let (high, low) = if register >= other_register { (register, other_register) } else { (other_register, register) };
+        let (high_max, low_max) = (high >> num_history_bits, low >> num_history_bits);
+        if low_max == 0 {
+            return high;
+        }
+        let shift = (high_max - low_max) as usize;
+        if shift > num_history_bits {
+            return high;
+        }
+        let low_history_with_max = ((1u64 << num_history_bits) | (low as u64 & ((1 << num_history_bits) - 1))) >> shift;
+        high | (low_history_with_max as u32 & ((1 << num_history_bits) - 1))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::*;
+
+    fn exaloglog_of(items: &[u64]) -> ExaLogLog {
+        let mut exaloglog = ExaLogLog::with_parameters(10, 2, 24, hash_function(1));
+        for &data in items {
+            exaloglog.read_data(data);
+        }
+        exaloglog
+    }
+
+    #[test]
+    fn estimates_are_accurate() {
+        // standard error is about 0.8 / sqrt(2^10) = 2.5% for ELL(2, 24)
+        for num_items in [100, 10_000, 1_000_000] {
+            let estimates = exaloglog_of(&random_items(2, num_items)).compute_estimates();
+            assert!((estimates - num_items as f64).abs() < 0.1 * num_items as f64, "{} items, estimate {}", num_items, estimates);
+        }
+    }
+
+    #[test]
+    fn merge_equals_inserting_everything() {
+        let (a_items, b_items) = overlapping_items(3);
+        let mut merged = exaloglog_of(&a_items);
+        merged.merge(&exaloglog_of(&b_items));
+        assert_eq!(merged.registers, exaloglog_of(&[a_items, b_items].concat()).registers);
+    }
+
+    #[test]
+    fn serialize_round_trips() {
+        let exaloglog = exaloglog_of(&random_items(4, 30_000));
+        let bytes = exaloglog.serialize();
+        let restored = ExaLogLog::deserialize(&bytes, hash_function(1)).unwrap();
+        assert_eq!(restored.registers, exaloglog.registers);
+        assert_eq!(restored.compute_estimates(), exaloglog.compute_estimates());
+        assert!(ExaLogLog::deserialize(&bytes[..bytes.len() - 1], hash_function(1)).is_none());
+    }
+
+    #[test]
+    fn deserialize_rejects_invalid_registers() {
+        let mut exaloglog = ExaLogLog::with_parameters(4, 2, 24, hash_function(5));
+        // the largest update value for 4 bucket bits and 2 extra bits is 59 * 4 = 236, the 8 bit field holds up to 255
+        exaloglog.registers[3] = 255 << 24;
+        assert!(ExaLogLog::deserialize(&exaloglog.serialize(), hash_function(5)).is_none());
+        // update value 2 can only have update value 1 in its history
+        exaloglog.registers[3] = (2 << 24) | 1;
+        assert!(ExaLogLog::deserialize(&exaloglog.serialize(), hash_function(5)).is_none());
+        exaloglog.registers[3] = (2 << 24) | (1 << 23);
+        assert!(ExaLogLog::deserialize(&exaloglog.serialize(), hash_function(5)).is_some());
+    }
+}
@@ -0,0 +1,192 @@
+use crate::universalhash::univesalhash::*;
+use crate::vanilla::hll::HLL;
+use rand::prelude::*;
+
+// hyperminhash (yu & weber 2017)
+// same bucket index / data bits split as HLL, but next to the leading zeros each register also keeps
+// the MantissaBits bits that follow the first one bit, so a register is the (truncated) minimum hash of its bucket
+// union cardinality comes from the leading zeros like hll, jaccard from the fraction of equal registers
+// minus the collisions we would expect between two unrelated sets of the same sizes
+pub struct HyperMinHash<const Precision: usize, const MantissaBits: usize> {
+    // (leading_zeros << MantissaBits) | (MANTISSA_MASK - mantissa), so the biggest register is the smallest hash
+    // leading_zeros counts the zeroed bucket bits too like HLLPP does, so 0 is never a valid non empty register
+    registers: Vec<u16>,
+    hasher: UniversalHashFunction,
+    seed: Option<u64>,
+}
+
+impl<const Precision: usize, const MantissaBits: usize> HyperMinHash<Precision, MantissaBits> {
+    const NUM_BUCKETS: usize = 1 << Precision;
+    const NUM_BUCKETS_F64: f64 = Self::NUM_BUCKETS as f64;
+    const MANTISSA_MASK: u16 = (1 << MantissaBits) - 1;
+
+    pub fn new() -> Self {
+        Self::with_seed(rand::rng().random())
+    }
+
+    pub fn with_seed(seed: u64) -> Self {
+        let mut hmh = Self::with_hash_function(UniversalHashFunctionsFamily::with_seed(64, seed).construct_new_hash_function_with_random_seeds());
+        hmh.seed = Some(seed);
+        hmh
+    }
+
+    pub fn with_hash_function(hasher: UniversalHashFunction) -> Self {
+        // leading zeros go up to 64 so they need 7 bits of the u16
+        assert!((4..=18).contains(&Precision), "hyperminhash needs a precision between 4 and 18");
+        assert!((1..=9).contains(&MantissaBits), "hyperminhash needs between 1 and 9 mantissa bits to fit a register in a u16");
+        Self {
+            registers: vec![0; Self::NUM_BUCKETS],
+            hasher,
+            seed: None,
+        }
+    }
+
+    pub fn seed(&self) -> Option<u64> {
+        self.seed
+    }
+
+    pub fn read_data(&mut self, data: u64) {
+        let hashed_data = self.hasher.hash64(data);
+        let (bucket_idx, data_bits) = (HLL::get_bucket_idx(Precision, hashed_data), HLL::get_data_bits(Precision, hashed_data));
+        let register = Self::encode(data_bits);
+        if register > self.registers[bucket_idx] {
+            self.registers[bucket_idx] = register;
+        }
+    }
+
+    // the leading zeros are hll ranks, so the union cardinality is the vanilla hll estimate of them
+    pub fn compute_estimates(&self) -> f64 {
+        let buckets: Vec<usize> = self.registers.iter().map(|&register| Self::rank(register) as usize).collect();
+        HLL::compute_estimates_from_buckets(&buckets)
+    }
+
+    pub fn size_in_bytes(&self) -> usize {
+        self.registers.len() * std::mem::size_of::<u16>()
+    }
+
+    pub fn merge(&mut self, other: &Self) {
+        assert!(self.hasher == other.hasher, "cannot merge hyperminhashes with different hash functions");
+        for (register, &other_register) in self.registers.iter_mut().zip(other.registers.iter()) {
+            *register = (*register).max(other_register);
+        }
+    }
+
+    pub fn union(&self, other: &Self) -> Self {
+        let mut union = Self::with_hash_function(self.hasher.clone());
+        union.seed = self.seed;
+        union.registers = self.registers.clone();
+        union.merge(other);
+        union
+    }
+
+    pub fn jaccard_estimates(&self, other: &Self) -> f64 {
+        assert!(self.hasher == other.hasher, "cannot compare hyperminhashes with different hash functions");
+        let (mut num_equal, mut num_non_empty) = (0usize, 0usize);
+        for (&register, &other_register) in self.registers.iter().zip(other.registers.iter()) {
+            if register != 0 || other_register != 0 {
+                num_non_empty += 1;
+                if register == other_register {
+                    num_equal += 1;
+                }
+            }
+        }
+        if num_non_empty == 0 {
+            return 0f64;
+        }
+        let expected_collisions = Self::expected_collisions(self.compute_estimates(), other.compute_estimates());
+        ((num_equal as f64 - expected_collisions) / num_non_empty as f64).max(0f64)
+    }
+
+    pub fn intersection_estimates(&self, other: &Self) -> f64 {
+        self.jaccard_estimates(other) * self.union(other).compute_estimates()
+    }
+
+    fn encode(data_bits: u64) -> u16 {
+        let leading_zeros = data_bits.leading_zeros() as usize;
+        let mantissa = if leading_zeros + 1 < 64 {
+            ((data_bits << (leading_zeros + 1)) >> (64 - MantissaBits)) as u16 & Self::MANTISSA_MASK
+        }
+        else {
+            0
+        };
+        ((leading_zeros as u16) << MantissaBits) | (Self::MANTISSA_MASK - mantissa)
+    }
+
+    fn rank(register: u16) -> i32 {
+        // position of the first one bit in the data bits, 0 for empty registers
+        if register == 0 {
+            0
+        }
+        else {
+            (register >> MantissaBits) as i32 - Precision as i32 + 1
+        }
+    }
+
+    fn expected_collisions(num_data: f64, other_num_data: f64) -> f64 {
+        // two unrelated sets put the same value in a register when both minimums fall in the same value range,
+        // a value range covers b1..b2 of the whole hash space so the minimum of n hashes lands there
+        // with probability (1 - b1)^n - (1 - b2)^n
+        let num_value_ranges = 1usize << MantissaBits;
+        let mut total = 0f64;
+        for rank in 1..=(64 - Precision as i32) {
+            let scale = 2f64.powi(-(Precision as i32) - rank);
+            for mantissa in 0..num_value_ranges {
+                let lower = scale * (1f64 + mantissa as f64 / num_value_ranges as f64);
+                let upper = scale * (1f64 + (mantissa + 1) as f64 / num_value_ranges as f64);
+                let probability = |n: f64| (n * (-lower).ln_1p()).exp() - (n * (-upper).ln_1p()).exp();
+                total += probability(num_data) * probability(other_num_data);
+            }
+        }
+        total * Self::NUM_BUCKETS_F64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::*;
+
+    type TestHyperMinHash = HyperMinHash<12, 6>;
+
+    fn hmh_of(items: &[u64]) -> TestHyperMinHash {
+        let mut hmh = TestHyperMinHash::with_hash_function(hash_function(1));
+        items.iter().for_each(|&data| hmh.read_data(data));
+        hmh
+    }
+
+    #[test]
+    fn estimates_are_accurate() {
+        // standard error is 1.04 / sqrt(2^12) = 1.6%
+        for num_items in [100, 5000, 1_000_000] {
+            let estimates = hmh_of(&random_items(2, num_items)).compute_estimates();
+            assert!((estimates - num_items as f64).abs() < 0.06 * num_items as f64, "{} items, estimate {}", num_items, estimates);
+        }
+    }
+
+    #[test]
+    fn merge_equals_inserting_everything() {
+        let (a_items, b_items) = overlapping_items(3);
+        let (mut merged, other, single) = (hmh_of(&a_items), hmh_of(&b_items), hmh_of(&[a_items, b_items].concat()));
+        assert_eq!(merged.union(&other).registers, single.registers);
+        merged.merge(&other);
+        assert_eq!(merged.registers, single.registers);
+    }
+
+    #[test]
+    fn set_operations_on_known_overlap() {
+        let (a_items, b_items) = overlapping_items(4);
+        let (a, b) = (hmh_of(&a_items), hmh_of(&b_items));
+        assert!((a.jaccard_estimates(&b) - 0.2).abs() < 0.04, "jaccard {}", a.jaccard_estimates(&b));
+        assert!((a.intersection_estimates(&b) - 20_000f64).abs() < 0.2 * 20_000f64, "intersection {}", a.intersection_estimates(&b));
+        assert!((a.union(&b).compute_estimates() - 100_000f64).abs() < 0.06 * 100_000f64, "union {}", a.union(&b).compute_estimates());
+        // disjoint sets only share the expected random collisions, which the estimator subtracts
+        let c = hmh_of(&random_items(5, 60_000));
+        assert!(a.jaccard_estimates(&c) < 0.02, "jaccard of disjoint sets {}", a.jaccard_estimates(&c));
+    }
+
+    #[test]
+    #[should_panic(expected = "between 1 and 9 mantissa bits")]
+    fn zero_mantissa_bits_are_rejected() {
+        HyperMinHash::<12, 0>::with_hash_function(hash_function(6));
+    }
+}
@@ -1,2 +1,5 @@
 pub mod hllpp;
-pub mod hllpprh;
\ No newline at end of file
+pub mod hllpprh;
+pub mod exaloglog;
+pub mod hyperminhash;
+pub mod registry;
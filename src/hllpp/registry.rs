@@ -0,0 +1,162 @@
+use crate::hllpp::hllpp::HLLPP;
+use rand::prelude::*;
+use crate::universalhash::univesalhash::*;
+use std::collections::HashMap;
+use std::hash::Hash;
+
+// one HLLPP per key, all sharing one hash function so registries can be merged
+// keys start in the sparse format and get promoted to the normal one as soon as their sparse entries
+// would take more memory than the normal buckets, which happens much earlier than HLLPP's own promotion
+// so cold keys stay a few bytes and hot keys never cost more than 2^Precision bytes
+pub struct HLLPPRegistry<K: Hash + Eq + Clone, const Precision: usize, const SparsePrecision: usize> {
+    sketches: HashMap<K, HLLPP<Precision, SparsePrecision>>,
+    hasher: UniversalHashFunction,
+    seed: Option<u64>,
+}
+
+impl<K: Hash + Eq + Clone, const Precision: usize, const SparsePrecision: usize> HLLPPRegistry<K, Precision, SparsePrecision> {
+    const NORMAL_SIZE_IN_BYTES: usize = 1 << Precision;
+
+    pub fn new() -> Self {
+        Self::with_seed(rand::rng().random())
+    }
+
+    pub fn with_seed(seed: u64) -> Self {
+        let mut registry = Self::with_hash_function(UniversalHashFunctionsFamily::with_seed(64, seed).construct_new_hash_function_with_random_seeds());
+        registry.seed = Some(seed);
+        registry
+    }
+
+    pub fn with_hash_function(hasher: UniversalHashFunction) -> Self {
+        Self {
+            sketches: HashMap::new(),
+            hasher,
+            seed: None,
+        }
+    }
+
+    pub fn seed(&self) -> Option<u64> {
+        self.seed
+    }
+
+    pub fn read_data(&mut self, key: &K, data: u64) {
+        let sketch = match self.sketches.get_mut(key) {
+            Some(sketch) => sketch,
+            None => self.sketches.entry(key.clone()).or_insert(HLLPP::with_hash_function(self.hasher.clone())),
+        };
+        sketch.read_data(data);
+        Self::promote_if_hot(sketch);
+    }
+
+    // 0 for keys that were never seen
+    pub fn compute_estimates(&self, key: &K) -> f64 {
+        self.sketches.get(key).map_or(0f64, |sketch| sketch.compute_estimates())
+    }
+
+    pub fn merge(&mut self, other: &Self) {
+        assert!(self.hasher == other.hasher, "cannot merge registries with different hash functions");
+        for (key, other_sketch) in &other.sketches {
+            let hasher = &self.hasher;
+            let sketch = self.sketches.entry(key.clone()).or_insert_with(|| HLLPP::with_hash_function(hasher.clone()));
+            sketch.merge(other_sketch);
+            Self::promote_if_hot(sketch);
+        }
+    }
+
+    pub fn get(&self, key: &K) -> Option<&HLLPP<Precision, SparsePrecision>> {
+        self.sketches.get(key)
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &K> {
+        self.sketches.keys()
+    }
+
+    pub fn len(&self) -> usize {
+        self.sketches.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sketches.is_empty()
+    }
+
+    pub fn num_sparse_keys(&self) -> usize {
+        self.sketches.values().filter(|sketch| sketch.is_sparse()).count()
+    }
+
+    pub fn num_normal_keys(&self) -> usize {
+        self.len() - self.num_sparse_keys()
+    }
+
+    // registers of every sketch plus the keys themselves, ignoring the hash map overhead
+    pub fn size_in_bytes(&self) -> usize {
+        self.sketches.values().map(|sketch| std::mem::size_of::<K>() + sketch.size_in_bytes()).sum()
+    }
+
+    fn promote_if_hot(sketch: &mut HLLPP<Precision, SparsePrecision>) {
+        if sketch.is_sparse() && sketch.size_in_bytes() >= Self::NORMAL_SIZE_IN_BYTES {
+            sketch.promote_to_normal();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::*;
+    use rand::rngs::StdRng;
+
+    type TestRegistry = HLLPPRegistry<u32, 14, 25>;
+
+    // key k gets 10 * (k + 1)^2 items, from 10 items for cold keys to 16k items for the hottest
+    fn keyed_items() -> Vec<(u32, u64)> {
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut keyed_items: Vec<(u32, u64)> = (0..40u32).flat_map(|key| (0..10 * (key as usize + 1).pow(2)).map(|_| (key, rng.random())).collect::<Vec<_>>()).collect();
+        keyed_items.shuffle(&mut rng);
+        keyed_items
+    }
+
+    #[test]
+    fn estimates_per_key_are_accurate() {
+        let mut registry = TestRegistry::with_hash_function(hash_function(2));
+        for (key, data) in keyed_items() {
+            registry.read_data(&key, data);
+        }
+        for key in 0..40u32 {
+            let num_items = 10f64 * (key as f64 + 1f64).powi(2);
+            // standard error is 1.04 / sqrt(2^14) = 0.8% once normal, much less while sparse
+            assert!((registry.compute_estimates(&key) - num_items).abs() < 0.04 * num_items, "key {}: estimate {}", key, registry.compute_estimates(&key));
+        }
+    }
+
+    #[test]
+    fn hot_keys_are_promoted() {
+        let mut registry = TestRegistry::with_hash_function(hash_function(2));
+        for (key, data) in keyed_items() {
+            registry.read_data(&key, data);
+        }
+        assert_eq!(registry.len(), 40);
+        assert_eq!(registry.compute_estimates(&40), 0f64);
+        // cold keys stay sparse, hot keys never cost more than their normal buckets
+        assert!(registry.num_sparse_keys() > 0 && registry.num_normal_keys() > 0);
+        assert!(registry.keys().all(|key| registry.get(key).unwrap().size_in_bytes() <= 1 << 14));
+    }
+
+    #[test]
+    fn merge_equals_inserting_everything() {
+        let (mut merged, mut other, mut single) = (TestRegistry::with_hash_function(hash_function(3)), TestRegistry::with_hash_function(hash_function(3)), TestRegistry::with_hash_function(hash_function(3)));
+        // some keys only go to one side so merge has to add them, the rest are split between both sides
+        for (i, (key, data)) in keyed_items().into_iter().enumerate() {
+            if key % 5 == 0 || (key % 5 != 1 && i % 3 == 0) { merged.read_data(&key, data) } else { other.read_data(&key, data) }
+            single.read_data(&key, data);
+        }
+        merged.merge(&other);
+        assert_eq!(merged.len(), single.len());
+        assert_eq!(merged.num_sparse_keys(), single.num_sparse_keys());
+        for key in single.keys() {
+            let (merged_sketch, single_sketch) = (merged.get(key).unwrap(), single.get(key).unwrap());
+            assert_eq!(merged_sketch.is_sparse(), single_sketch.is_sparse(), "key {}", key);
+            assert_eq!(merged_sketch.size_in_bytes(), single_sketch.size_in_bytes(), "key {}", key);
+            assert_eq!(merged.compute_estimates(key), single.compute_estimates(key), "key {}", key);
+        }
+    }
+}
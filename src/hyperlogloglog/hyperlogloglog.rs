@@ -0,0 +1,219 @@
+use crate::universalhash::univesalhash::*;
+use rand::prelude::*;
+use crate::vanilla::hll::HLL;
+use std::collections::HashMap;
+
+// karppa & pagh's hyperlogloglog (2022)
+// same registers as hll (max rank, the position of the first one bit after the bucket bits),
+// but stored as 3 bit offsets from a shared base
+// registers that do not fit in base..base + 6 get the offset 7 and their full value goes into a sparse exception map
+// nothing is lost, so the estimates are exactly the ones hll computes from the same registers
+pub struct HyperLogLogLog {
+    num_bucket_bits: usize,
+    base: u8,
+    offsets: Vec<u64>,
+    exceptions: HashMap<usize, u8>,
+    num_at_base: usize,
+    rebase_threshold: usize,
+    hash_function: UniversalHashFunction,
+    seed: Option<u64>,
+}
+
+impl HyperLogLogLog {
+    const OFFSET_NUM_BITS: usize = 3;
+    const EXCEPTION_OFFSET: u8 = (1 << Self::OFFSET_NUM_BITS) - 1;
+    // ranks are at most 64
+    const REGISTER_NUM_BITS: usize = 7;
+
+    pub fn new(num_bucket_bits: usize) -> Self {
+        Self::with_seed(num_bucket_bits, rand::rng().random())
+    }
+
+    pub fn with_seed(num_bucket_bits: usize, seed: u64) -> Self {
+        let mut hlll = Self::with_hash_function(num_bucket_bits, UniversalHashFunctionsFamily::with_seed(64, seed).construct_new_hash_function_with_random_seeds());
+        hlll.seed = Some(seed);
+        hlll
+    }
+
+    pub fn with_hash_function(num_bucket_bits: usize, hash_function: UniversalHashFunction) -> Self {
+        let num_buckets = 1 << num_bucket_bits;
+        Self {
+            num_bucket_bits,
+            base: 0,
+            offsets: vec![0; (num_buckets * Self::OFFSET_NUM_BITS).div_ceil(64)],
+            exceptions: HashMap::new(),
+            num_at_base: num_buckets,
+            rebase_threshold: Self::min_rebase_threshold(num_buckets),
+            hash_function,
+            seed: None,
+        }
+    }
+
+    pub fn seed(&self) -> Option<u64> {
+        self.seed
+    }
+
+    pub fn read_data(&mut self, data: u64) {
+        let hashed_data = self.hash_function.hash64(data);
+        let (bucket_idx, data_bits) = (HLL::get_bucket_idx(self.num_bucket_bits, hashed_data), HLL::get_data_bits(self.num_bucket_bits, hashed_data));
+        let rank = HLL::get_rank(self.num_bucket_bits, data_bits) as u8;
+        let register = self.get_register(bucket_idx);
+        if rank > register {
+            self.set_register(bucket_idx, rank);
+            if register == self.base {
+                self.num_at_base -= 1;
+            }
+            // rebase once the registers have drifted away from the base, i.e. too many exceptions
+            // or no register left at the base (so the base can at least move up by 1 for free)
+            if self.exceptions.len() > self.rebase_threshold || self.num_at_base == 0 {
+                self.rebase();
+            }
+        }
+    }
+
+    // keeps the max of every register, both sketches need the same hash function
+    pub fn merge(&mut self, other: &Self) {
+        assert!(self.num_bucket_bits == other.num_bucket_bits && self.hash_function == other.hash_function, "cannot merge hyperlogloglogs with different precisions or hash functions");
+        for bucket_idx in 0..self.num_buckets() {
+            let other_register = other.get_register(bucket_idx);
+            if other_register > self.get_register(bucket_idx) {
+                self.set_register(bucket_idx, other_register);
+            }
+        }
+        // every register may have moved, so pick the base again from scratch
+        self.rebase();
+    }
+
+    pub fn compute_estimates(&self) -> f64 {
+        let buckets: Vec<usize> = (0..self.num_buckets()).map(|bucket_idx| self.get_register(bucket_idx) as usize).collect();
+        HLL::compute_estimates_from_buckets(&buckets)
+    }
+
+    pub fn size_in_bits(&self) -> usize {
+        // base + dense offsets + (bucket idx, register) per exception
+        Self::REGISTER_NUM_BITS + self.num_buckets() * Self::OFFSET_NUM_BITS + self.exceptions.len() * (self.num_bucket_bits + Self::REGISTER_NUM_BITS)
+    }
+
+    fn num_buckets(&self) -> usize {
+        1 << self.num_bucket_bits
+    }
+
+    fn get_register(&self, bucket_idx: usize) -> u8 {
+        let offset = self.get_offset(bucket_idx);
+        if offset == Self::EXCEPTION_OFFSET {
+            self.exceptions[&bucket_idx]
+        }
+        else {
+            self.base + offset
+        }
+    }
+
+    fn set_register(&mut self, bucket_idx: usize, register: u8) {
+        if register >= self.base && register - self.base < Self::EXCEPTION_OFFSET {
+            self.set_offset(bucket_idx, register - self.base);
+            self.exceptions.remove(&bucket_idx);
+        }
+        else {
+            self.set_offset(bucket_idx, Self::EXCEPTION_OFFSET);
+            self.exceptions.insert(bucket_idx, register);
+        }
+    }
+
+    fn rebase(&mut self) {
+        // pick the base that leaves the fewest registers outside of base..base + 6 and rewrite every register
+        let registers: Vec<u8> = (0..self.num_buckets()).map(|bucket_idx| self.get_register(bucket_idx)).collect();
+        let mut register_counts = [0usize; 1 << Self::REGISTER_NUM_BITS];
+        for &register in &registers {
+            register_counts[register as usize] += 1;
+        }
+        let num_in_range = |base: usize| -> usize {
+            register_counts[base..(base + Self::EXCEPTION_OFFSET as usize).min(register_counts.len())].iter().sum()
+        };
+        // ties go to the larger base, so the base always has at least 1 register on it
+        let best_base = (0..register_counts.len()).max_by_key(|&base| num_in_range(base)).unwrap();
+        self.base = best_base as u8;
+        self.num_at_base = register_counts[best_base];
+        self.exceptions.clear();
+        for (bucket_idx, &register) in registers.iter().enumerate() {
+            self.set_register(bucket_idx, register);
+        }
+        // wait for a few more exceptions before trying again, so the rebasing cost is amortized over them
+        self.rebase_threshold = self.exceptions.len() + Self::min_rebase_threshold(self.num_buckets());
+    }
+
+    fn min_rebase_threshold(num_buckets: usize) -> usize {
+        (num_buckets / 32).max(1)
+    }
+
+    fn get_offset(&self, bucket_idx: usize) -> u8 {
+        let bit_idx = bucket_idx * Self::OFFSET_NUM_BITS;
+        let (word_idx, bit_shift) = (bit_idx / 64, bit_idx % 64);
+        let mut offset = self.offsets[word_idx] >> bit_shift;
+        if bit_shift + Self::OFFSET_NUM_BITS > 64 {
+            offset |= self.offsets[word_idx + 1] << (64 - bit_shift);
+        }
+        (offset & Self::EXCEPTION_OFFSET as u64) as u8
+    }
+
+    fn set_offset(&mut self, bucket_idx: usize, offset: u8) {
+        let bit_idx = bucket_idx * Self::OFFSET_NUM_BITS;
+        let (word_idx, bit_shift) = (bit_idx / 64, bit_idx % 64);
+        let mask = Self::EXCEPTION_OFFSET as u64;
+        self.offsets[word_idx] = (self.offsets[word_idx] & !(mask << bit_shift)) | ((offset as u64) << bit_shift);
+        if bit_shift + Self::OFFSET_NUM_BITS > 64 {
+            let num_low_bits = 64 - bit_shift;
+            self.offsets[word_idx + 1] = (self.offsets[word_idx + 1] & !(mask >> num_low_bits)) | ((offset as u64) >> num_low_bits);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::*;
+
+    #[test]
+    fn estimates_equal_hll() {
+        for num_items in [0, 100, 5000, 30_000, 200_000] {
+            let mut hlll = HyperLogLogLog::with_hash_function(12, hash_function(1));
+            let mut hll = HLL::with_hash_function(12, hash_function(1));
+            for data in random_items(2, num_items) {
+                hlll.read_data(data);
+                hll.read_data(data);
+            }
+            let registers: Vec<usize> = (0..hlll.num_buckets()).map(|bucket_idx| hlll.get_register(bucket_idx) as usize).collect();
+            assert_eq!(&registers, hll.buckets(), "{} items", num_items);
+            assert_eq!(hlll.compute_estimates(), hll.get_cardinality(), "{} items", num_items);
+        }
+    }
+
+    #[test]
+    fn estimates_are_accurate() {
+        let mut hlll = HyperLogLogLog::with_seed(12, 3);
+        for data in random_items(4, 1_000_000) {
+            hlll.read_data(data);
+        }
+        // standard error is 1.04 / sqrt(2^12) = 1.6%
+        assert!((hlll.compute_estimates() - 1e6).abs() < 0.06 * 1e6, "estimate {}", hlll.compute_estimates());
+        assert!(hlll.size_in_bits() < 6 * (1 << 12), "{} bits", hlll.size_in_bits());
+    }
+
+    #[test]
+    fn merge_equals_inserting_everything() {
+        let hlll_of = |items: &[u64]| {
+            let mut hlll = HyperLogLogLog::with_hash_function(10, hash_function(5));
+            for &data in items {
+                hlll.read_data(data);
+            }
+            hlll
+        };
+        let (a_items, b_items) = overlapping_items(6);
+        let mut merged = hlll_of(&a_items);
+        merged.merge(&hlll_of(&b_items));
+        let single = hlll_of(&[a_items, b_items].concat());
+        for bucket_idx in 0..single.num_buckets() {
+            assert_eq!(merged.get_register(bucket_idx), single.get_register(bucket_idx));
+        }
+        assert_eq!(merged.compute_estimates(), single.compute_estimates());
+    }
+}
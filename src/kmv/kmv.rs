@@ -0,0 +1,150 @@
+use crate::universalhash::univesalhash::*;
+use rand::prelude::*;
+use std::collections::BTreeSet;
+
+// k minimum values / bottom-k sketch (bar-yossef et al. 2002, beyer et al. 2007)
+// keeps the k smallest distinct hash values, the k-th smallest one tells us how dense the hashes are
+// since the sketch is a uniform sample of the hashes it also supports intersection and jaccard
+pub struct KMV {
+    k: usize,
+    min_values: BTreeSet<u64>,
+    hash_function: UniversalHashFunction,
+    seed: Option<u64>,
+}
+
+impl KMV {
+    pub fn new(k: usize) -> Self {
+        Self::with_seed(k, rand::rng().random())
+    }
+
+    pub fn with_seed(k: usize, seed: u64) -> Self {
+        let mut kmv = Self::with_hash_function(k, UniversalHashFunctionsFamily::with_seed(64, seed).construct_new_hash_function_with_random_seeds());
+        kmv.seed = Some(seed);
+        kmv
+    }
+
+    pub fn with_hash_function(k: usize, hash_function: UniversalHashFunction) -> Self {
+        assert!(k > 1, "kmv needs k > 1 for the unbiased estimator");
+        Self {
+            k,
+            min_values: BTreeSet::new(),
+            hash_function,
+            seed: None,
+        }
+    }
+
+    pub fn seed(&self) -> Option<u64> {
+        self.seed
+    }
+
+    pub fn read_data(&mut self, data: u64) {
+        let hashed_data = self.hash_function.hash64(data);
+        self.insert_hash(hashed_data);
+    }
+
+    pub fn compute_estimates(&self) -> f64 {
+        if self.min_values.len() < self.k {
+            // we have seen every distinct hash so far
+            return self.min_values.len() as f64;
+        }
+        (self.k - 1) as f64 / Self::normalize(*self.min_values.last().unwrap())
+    }
+
+    pub fn merge(&mut self, other: &Self) {
+        self.assert_compatible(other);
+        for &hashed_data in &other.min_values {
+            self.insert_hash(hashed_data);
+        }
+    }
+
+    pub fn union(&self, other: &Self) -> Self {
+        self.assert_compatible(other);
+        let mut union = Self::with_hash_function(self.k, self.hash_function.clone());
+        union.seed = self.seed;
+        union.min_values = self.min_values.union(&other.min_values).take(self.k).copied().collect();
+        union
+    }
+
+    pub fn jaccard_estimates(&self, other: &Self) -> f64 {
+        // the bottom-k of the union is a uniform sample of the union
+        // so the fraction of it that is in both sketches estimates the jaccard index
+        // every hash in that sample is below both k-th minimums so the membership checks are exact
+        let union = self.union(other);
+        if union.min_values.is_empty() {
+            return 0f64;
+        }
+        let num_in_both = union.min_values.iter()
+            .filter(|hashed_data| self.min_values.contains(hashed_data) && other.min_values.contains(hashed_data))
+            .count();
+        num_in_both as f64 / union.min_values.len() as f64
+    }
+
+    pub fn intersection_estimates(&self, other: &Self) -> f64 {
+        self.jaccard_estimates(other) * self.union(other).compute_estimates()
+    }
+
+    fn insert_hash(&mut self, hashed_data: u64) {
+        if self.min_values.len() < self.k {
+            self.min_values.insert(hashed_data);
+        }
+        else if hashed_data < *self.min_values.last().unwrap() && self.min_values.insert(hashed_data) {
+            self.min_values.pop_last();
+        }
+    }
+
+    fn normalize(hashed_data: u64) -> f64 {
+        // map the hash onto (0, 1]
+        (hashed_data as f64 + 1f64) / 2f64.powi(64)
+    }
+
+    fn assert_compatible(&self, other: &Self) {
+        assert_eq!(self.k, other.k, "cannot combine kmvs with different k");
+        assert!(self.hash_function == other.hash_function, "cannot combine kmvs with different hash functions");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::*;
+
+    fn kmv_of(k: usize, items: &[u64]) -> KMV {
+        let mut kmv = KMV::with_hash_function(k, hash_function(1));
+        items.iter().for_each(|&data| kmv.read_data(data));
+        kmv
+    }
+
+    #[test]
+    fn exact_below_k() {
+        let items = random_items(2, 1000);
+        assert_eq!(kmv_of(1024, &[items.clone(), items].concat()).compute_estimates(), 1000f64);
+    }
+
+    #[test]
+    fn estimates_are_accurate() {
+        // standard error is 1 / sqrt(k - 2) = 1.6%
+        let estimates = kmv_of(4096, &random_items(3, 1_000_000)).compute_estimates();
+        assert!((estimates - 1e6).abs() < 0.06 * 1e6, "estimate {}", estimates);
+    }
+
+    #[test]
+    fn merge_and_union_equal_inserting_everything() {
+        let (a_items, b_items) = overlapping_items(4);
+        let (a, b, single) = (kmv_of(4096, &a_items), kmv_of(4096, &b_items), kmv_of(4096, &[a_items, b_items].concat()));
+        assert_eq!(a.union(&b).min_values, single.min_values);
+        let mut merged = a;
+        merged.merge(&b);
+        assert_eq!(merged.min_values, single.min_values);
+    }
+
+    #[test]
+    fn set_operations_on_known_overlap() {
+        let (a_items, b_items) = overlapping_items(5);
+        let (a, b) = (kmv_of(4096, &a_items), kmv_of(4096, &b_items));
+        // the jaccard estimate is a binomial fraction of k, standard error sqrt(0.2 * 0.8 / k) = 0.006
+        assert!((a.jaccard_estimates(&b) - 0.2).abs() < 0.03, "jaccard {}", a.jaccard_estimates(&b));
+        assert!((a.intersection_estimates(&b) - 20_000f64).abs() < 0.15 * 20_000f64, "intersection {}", a.intersection_estimates(&b));
+        assert!((a.union(&b).compute_estimates() - 100_000f64).abs() < 0.06 * 100_000f64, "union {}", a.union(&b).compute_estimates());
+        assert_eq!(a.jaccard_estimates(&a), 1f64);
+    }
+}
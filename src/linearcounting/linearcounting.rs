@@ -0,0 +1,128 @@
+use crate::universalhash::univesalhash::*;
+use rand::prelude::*;
+
+// whang, vander-zanden & taylor linear counting (1990)
+// hash every item into a bitmap and estimate from the fraction of bits that are still 0
+// very accurate for small cardinalities but the bitmap has to grow linearly with the cardinality
+pub struct LinearCounting {
+    num_bits: usize,
+    num_empty_bits: usize,
+    bitmap: Vec<u64>,
+    hash_function: UniversalHashFunction,
+    seed: Option<u64>,
+}
+
+impl LinearCounting {
+    pub fn new(num_bits: usize) -> Self {
+        Self::with_seed(num_bits, rand::rng().random())
+    }
+
+    pub fn with_seed(num_bits: usize, seed: u64) -> Self {
+        let mut linear_counting = Self::with_hash_function(num_bits, UniversalHashFunctionsFamily::with_seed(64, seed).construct_new_hash_function_with_random_seeds());
+        linear_counting.seed = Some(seed);
+        linear_counting
+    }
+
+    pub fn with_hash_function(num_bits: usize, hash_function: UniversalHashFunction) -> Self {
+        assert!(num_bits > 0, "linear counting needs at least 1 bit");
+        Self {
+            num_bits,
+            num_empty_bits: num_bits,
+            bitmap: vec![0; num_bits.div_ceil(64)],
+            hash_function,
+            seed: None,
+        }
+    }
+
+    pub fn seed(&self) -> Option<u64> {
+        self.seed
+    }
+
+    pub fn read_data(&mut self, data: u64) {
+        let hashed_data = self.hash_function.hash64(data);
+        let bit_idx = Self::get_bit_idx(self.num_bits, hashed_data);
+        let (word_idx, bit_mask) = (bit_idx / 64, 1u64 << (bit_idx % 64));
+        if self.bitmap[word_idx] & bit_mask == 0 {
+            self.bitmap[word_idx] |= bit_mask;
+            self.num_empty_bits -= 1;
+        }
+    }
+
+    pub fn compute_estimates(&self) -> f64 {
+        // a saturated bitmap only tells us that the cardinality is "a lot more than num_bits"
+        if self.is_saturated() {
+            return f64::INFINITY;
+        }
+        Self::linear_counting(self.num_bits, self.num_empty_bits)
+    }
+
+    pub fn is_saturated(&self) -> bool {
+        self.num_empty_bits == 0
+    }
+
+    pub fn fill_ratio(&self) -> f64 {
+        (self.num_bits - self.num_empty_bits) as f64 / self.num_bits as f64
+    }
+
+    pub fn merge(&mut self, other: &Self) {
+        assert_eq!(self.num_bits, other.num_bits, "cannot merge linear countings with different bitmap sizes");
+        assert!(self.hash_function == other.hash_function, "cannot merge linear countings with different hash functions");
+        for (word, &other_word) in self.bitmap.iter_mut().zip(other.bitmap.iter()) {
+            *word |= other_word;
+        }
+        self.num_empty_bits = self.num_bits - self.bitmap.iter().map(|word| word.count_ones() as usize).sum::<usize>();
+    }
+
+    fn get_bit_idx(num_bits: usize, data: u64) -> usize {
+        // map the hash onto 0..num_bits with a multiply-shift instead of a modulo
+        // so the bitmap size does not have to be a power of 2
+        (((data as u128) * (num_bits as u128)) >> 64) as usize
+    }
+
+    // also the small range estimate of the sketches that count their empty registers
+    pub(crate) fn linear_counting(num_bits: usize, num_empty_bits: usize) -> f64 {
+        let num_bits_f64 = num_bits as f64;
+        let num_empty_bits_f64 = num_empty_bits as f64;
+        num_bits_f64 * f64::ln(num_bits_f64 / num_empty_bits_f64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::*;
+
+    fn linear_counting_of(num_bits: usize, items: &[u64]) -> LinearCounting {
+        let mut linear_counting = LinearCounting::with_hash_function(num_bits, hash_function(1));
+        items.iter().for_each(|&data| linear_counting.read_data(data));
+        linear_counting
+    }
+
+    #[test]
+    fn estimates_are_accurate() {
+        // 10_000 bits is not a power of 2 on purpose
+        // the standard error is sqrt(m (e^t - t - 1)) / n for a load t = n / m, 1.3% at t = 3
+        for num_items in [0, 100, 5000, 30_000] {
+            let estimates = linear_counting_of(10_000, &random_items(2, num_items)).compute_estimates();
+            assert!((estimates - num_items as f64).abs() < 0.05 * num_items as f64 + 1f64, "{} items, estimate {}", num_items, estimates);
+        }
+    }
+
+    #[test]
+    fn saturated_bitmaps_estimate_infinity() {
+        let linear_counting = linear_counting_of(100, &random_items(3, 10_000));
+        assert!(linear_counting.is_saturated());
+        assert_eq!(linear_counting.fill_ratio(), 1f64);
+        assert_eq!(linear_counting.compute_estimates(), f64::INFINITY);
+    }
+
+    #[test]
+    fn merge_equals_inserting_everything() {
+        let (a_items, b_items) = overlapping_items(4);
+        let mut merged = linear_counting_of(200_000, &a_items);
+        merged.merge(&linear_counting_of(200_000, &b_items));
+        let single = linear_counting_of(200_000, &[a_items, b_items].concat());
+        assert_eq!(merged.bitmap, single.bitmap);
+        assert_eq!(merged.num_empty_bits, single.num_empty_bits);
+    }
+}
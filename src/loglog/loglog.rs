@@ -0,0 +1,131 @@
+use crate::universalhash::univesalhash::*;
+use crate::vanilla::hll::HLL;
+use rand::prelude::*;
+use crate::linearcounting::linearcounting::LinearCounting;
+
+// durand-flajolet loglog (2003)
+// same bucket layout as hll (top bits pick the register, register keeps the max rank)
+// but the registers are combined with a geometric mean instead of a harmonic mean
+pub struct LogLog {
+    num_bucket_bits: usize,
+    num_buckets: usize,
+    bias_correction_value: f64,
+    buckets: Vec<u8>,
+    hash_function: UniversalHashFunction,
+    seed: Option<u64>,
+}
+
+impl LogLog {
+    // alpha_inf from the paper
+    const ALPHA_INF: f64 = 0.39701;
+    // loglog only becomes unbiased once every register has seen a few items (x160 too high at n = 10 for m = 4096,
+    // +11% at n = m, +2% at n = 2m), while linear counting on the empty registers stays within its ~2% standard error
+    // up to about 3m and falls apart past that as the last empty registers fill up
+    pub(crate) const LINEAR_COUNTING_MAX_LOAD: f64 = 3f64;
+
+    pub fn new(num_bucket_bits: usize) -> Self {
+        Self::with_seed(num_bucket_bits, rand::rng().random())
+    }
+
+    pub fn with_seed(num_bucket_bits: usize, seed: u64) -> Self {
+        assert!(num_bucket_bits >= 1, "loglog needs at least 1 bucket bit");
+        let num_buckets = 1 << num_bucket_bits;
+        Self {
+            num_bucket_bits,
+            num_buckets,
+            bias_correction_value: Self::compute_bias_correction_value(num_buckets),
+            buckets: vec![0; num_buckets],
+            hash_function: UniversalHashFunctionsFamily::with_seed(64, seed).construct_new_hash_function_with_random_seeds(),
+            seed: Some(seed),
+        }
+    }
+
+    pub fn seed(&self) -> Option<u64> {
+        self.seed
+    }
+
+    pub fn read_data(&mut self, data: u64) {
+        let hashed_data = self.hash_function.hash64(data);
+        let bucket_idx = HLL::get_bucket_idx(self.num_bucket_bits, hashed_data);
+        let rank = HLL::get_rank(self.num_bucket_bits, HLL::get_data_bits(self.num_bucket_bits, hashed_data)) as u8;
+        if rank > self.buckets[bucket_idx] {
+            self.buckets[bucket_idx] = rank;
+        }
+    }
+
+    // keeps the max of every register, both sketches need the same hash function
+    pub fn merge(&mut self, other: &Self) {
+        assert!(self.num_bucket_bits == other.num_bucket_bits && self.hash_function == other.hash_function, "cannot merge loglogs with different precisions or hash functions");
+        for (bucket, &other_bucket) in self.buckets.iter_mut().zip(other.buckets.iter()) {
+            *bucket = (*bucket).max(other_bucket);
+        }
+    }
+
+    pub fn compute_estimates(&self) -> f64 {
+        let num_buckets_f64 = self.num_buckets as f64;
+        if let Some(linear_counting_estimates) = Self::compute_small_range_estimates(&self.buckets) {
+            return linear_counting_estimates;
+        }
+        let mean_rank = self.buckets.iter().map(|&rank| rank as f64).sum::<f64>() / num_buckets_f64;
+        self.bias_correction_value * num_buckets_f64 * 2f64.powf(mean_rank)
+    }
+
+    // linear counting while it is the better estimate, shared with superloglog
+    pub(crate) fn compute_small_range_estimates(buckets: &[u8]) -> Option<f64> {
+        let num_buckets_f64 = buckets.len() as f64;
+        let num_empty_buckets = buckets.iter().filter(|&&rank| rank == 0).count();
+        if num_empty_buckets == 0 {
+            return None;
+        }
+        let linear_counting_estimates = LinearCounting::linear_counting(buckets.len(), num_empty_buckets);
+        if linear_counting_estimates <= Self::LINEAR_COUNTING_MAX_LOAD * num_buckets_f64 {
+            Some(linear_counting_estimates)
+        }
+        else {
+            None
+        }
+    }
+
+    fn compute_bias_correction_value(num_buckets: usize) -> f64 {
+        // asymptotic expansion of alpha_m from the paper
+        // alpha_m ~ alpha_inf - (2 pi^2 + ln^2 2) / (48 m)
+        let ln_2 = std::f64::consts::LN_2;
+        Self::ALPHA_INF - (2f64 * std::f64::consts::PI.powi(2) + ln_2 * ln_2) / (48f64 * num_buckets as f64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::*;
+
+    #[test]
+    fn estimates_are_accurate_over_every_range() {
+        // standard error is 1.30 / sqrt(2^12) = 2%, 10 and 1000 are linear counting, 1m is the geometric mean
+        for num_items in [10, 1000, 8000, 1_000_000] {
+            let mut loglog = LogLog::with_seed(12, 1);
+            for data in random_items(2, num_items) {
+                loglog.read_data(data);
+            }
+            let estimates = loglog.compute_estimates();
+            assert!((estimates - num_items as f64).abs() < 0.08 * num_items as f64, "{} items, estimate {}", num_items, estimates);
+        }
+    }
+
+    #[test]
+    fn merge_equals_inserting_everything() {
+        let (mut merged, mut other, mut single) = (LogLog::with_seed(10, 3), LogLog::with_seed(10, 3), LogLog::with_seed(10, 3));
+        for (i, data) in random_items(4, 50_000).into_iter().enumerate() {
+            if i % 3 == 0 { merged.read_data(data) } else { other.read_data(data) }
+            single.read_data(data);
+        }
+        merged.merge(&other);
+        assert_eq!(merged.buckets, single.buckets);
+    }
+
+    #[test]
+    #[should_panic]
+    fn zero_bucket_bits_panic() {
+        LogLog::with_seed(0, 1);
+    }
+}
@@ -0,0 +1,2 @@
+pub mod loglog;
+pub mod superloglog;
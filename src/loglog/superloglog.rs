@@ -0,0 +1,137 @@
+use crate::universalhash::univesalhash::*;
+use crate::vanilla::hll::HLL;
+use crate::loglog::loglog::LogLog;
+use rand::prelude::*;
+
+// durand-flajolet superloglog (2003)
+// loglog with the truncation rule: only the smallest 70% of the registers go into the mean
+// which throws away the few huge outliers that blow up the geometric mean
+pub struct SuperLogLog {
+    num_bucket_bits: usize,
+    num_buckets: usize,
+    num_kept_buckets: usize,
+    buckets: Vec<u8>,
+    hash_function: UniversalHashFunction,
+    seed: Option<u64>,
+}
+
+impl SuperLogLog {
+    const TRUNCATION_RATIO: f64 = 0.7;
+    // the paper gives alpha_inf = 0.764 for the 70% truncation rule, which it derives together with the restriction rule
+    // this sketch does not apply; here 0.764 is 0.74% low on average (mean estimate / n = 0.9926 for m = 2^10, 2^12 and 2^14,
+    // 16 points per doubling of n, 200 sketches each), so the constant is rescaled by that, see bias_correction_value_is_calibrated
+    // ranks are integers, so the truncated mean and with it the estimate still oscillate by about +-1.2% with log2(n / m)
+    const BIAS_CORRECTION_VALUE: f64 = 0.764 / 0.9926;
+
+    pub fn new(num_bucket_bits: usize) -> Self {
+        Self::with_seed(num_bucket_bits, rand::rng().random())
+    }
+
+    pub fn with_seed(num_bucket_bits: usize, seed: u64) -> Self {
+        assert!(num_bucket_bits >= 1, "superloglog needs at least 1 bucket bit");
+        let num_buckets = 1 << num_bucket_bits;
+        Self {
+            num_bucket_bits,
+            num_buckets,
+            num_kept_buckets: ((num_buckets as f64) * Self::TRUNCATION_RATIO).floor().max(1f64) as usize,
+            buckets: vec![0; num_buckets],
+            hash_function: UniversalHashFunctionsFamily::with_seed(64, seed).construct_new_hash_function_with_random_seeds(),
+            seed: Some(seed),
+        }
+    }
+
+    pub fn seed(&self) -> Option<u64> {
+        self.seed
+    }
+
+    pub fn read_data(&mut self, data: u64) {
+        let hashed_data = self.hash_function.hash64(data);
+        let bucket_idx = HLL::get_bucket_idx(self.num_bucket_bits, hashed_data);
+        let rank = HLL::get_rank(self.num_bucket_bits, HLL::get_data_bits(self.num_bucket_bits, hashed_data)) as u8;
+        if rank > self.buckets[bucket_idx] {
+            self.buckets[bucket_idx] = rank;
+        }
+    }
+
+    // keeps the max of every register, both sketches need the same hash function
+    pub fn merge(&mut self, other: &Self) {
+        assert!(self.num_bucket_bits == other.num_bucket_bits && self.hash_function == other.hash_function, "cannot merge superloglogs with different precisions or hash functions");
+        for (bucket, &other_bucket) in self.buckets.iter_mut().zip(other.buckets.iter()) {
+            *bucket = (*bucket).max(other_bucket);
+        }
+    }
+
+    pub fn compute_estimates(&self) -> f64 {
+        // the truncated mean is even more biased than loglog's for small n, same fallback to linear counting
+        if let Some(linear_counting_estimates) = LogLog::compute_small_range_estimates(&self.buckets) {
+            return linear_counting_estimates;
+        }
+        // ranks are small so counting sort them instead of sorting the whole register array
+        let mut rank_counts = [0usize; 66];
+        for &rank in &self.buckets {
+            rank_counts[rank as usize] += 1;
+        }
+        let mut num_remaining = self.num_kept_buckets;
+        let mut total_rank = 0usize;
+        for (rank, &count) in rank_counts.iter().enumerate() {
+            let num_taken = count.min(num_remaining);
+            total_rank += rank * num_taken;
+            num_remaining -= num_taken;
+            if num_remaining == 0 {
+                break;
+            }
+        }
+        let truncated_mean_rank = total_rank as f64 / self.num_kept_buckets as f64;
+        Self::BIAS_CORRECTION_VALUE * (self.num_buckets as f64) * 2f64.powf(truncated_mean_rank)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::*;
+
+    #[test]
+    fn estimates_are_accurate_over_every_range() {
+        // standard error is 1.05 / sqrt(2^12) = 1.6%, 10 and 1000 are linear counting, the rest the truncated mean
+        for num_items in [10, 1000, 16_000, 1_000_000] {
+            let mut superloglog = SuperLogLog::with_seed(12, 1);
+            for data in random_items(2, num_items) {
+                superloglog.read_data(data);
+            }
+            let estimates = superloglog.compute_estimates();
+            assert!((estimates - num_items as f64).abs() < 0.08 * num_items as f64, "{} items, estimate {}", num_items, estimates);
+        }
+    }
+
+    #[test]
+    fn bias_correction_value_is_calibrated() {
+        // the calibration above with fewer sketches: averaged over one doubling of n the estimate is unbiased
+        let num_bucket_bits = 10;
+        let mut total_ratio = 0f64;
+        let (num_points, num_runs) = (16, 10);
+        for point in 0..num_points {
+            let num_items = ((1 << num_bucket_bits) as f64 * 2f64.powf(5f64 + point as f64 / num_points as f64)) as usize;
+            for run in 0..num_runs {
+                let mut superloglog = SuperLogLog::with_seed(num_bucket_bits, point * num_runs + run);
+                for data in random_items(1000 + point * num_runs + run, num_items) {
+                    superloglog.read_data(data);
+                }
+                total_ratio += superloglog.compute_estimates() / num_items as f64;
+            }
+        }
+        let mean_ratio = total_ratio / (num_points * num_runs) as f64;
+        assert!((mean_ratio - 1f64).abs() < 0.005, "mean estimate / n = {}", mean_ratio);
+    }
+
+    #[test]
+    fn merge_equals_inserting_everything() {
+        let (mut merged, mut other, mut single) = (SuperLogLog::with_seed(10, 3), SuperLogLog::with_seed(10, 3), SuperLogLog::with_seed(10, 3));
+        for (i, data) in random_items(4, 50_000).into_iter().enumerate() {
+            if i % 3 == 0 { merged.read_data(data) } else { other.read_data(data) }
+            single.read_data(data);
+        }
+        merged.merge(&other);
+        assert_eq!(merged.buckets, single.buckets);
+    }
+}
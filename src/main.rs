@@ -1,15 +1,70 @@
 mod vanilla;
 mod universalhash;
+#[cfg(test)]
+mod testutil;
 mod benchmark;
 mod hllpp;
+mod loglog;
+mod pcsa;
+mod linearcounting;
+mod kmv;
+mod ultraloglog;
+mod cpc;
+mod theta;
+mod hyperlogloglog;
+mod cvm;
+mod setsketch;
+mod rollup;
 
 use vlq::{ReadVlqExt, WriteVlqExt};
 
 fn main() {
     println!("Hello, world!");
-    // benchmark::vanilla::run_benchmark_speed();
-    // benchmark::vanilla::run_benchmark_accuracy();
-    benchmark::hllpp::benchmarK_accuracy_hll();
-    // benchmark::hllpp::benchmarK_accuracy_hllpp();
-    // benchmark::hllpp::benchmarK_accuracy_hllpprh();
+    // every benchmark derives its sketch seeds and its data from the master seed,
+    // so passing the printed seed back as the first argument regenerates the exact same numbers
+    let master_seed: u64 = std::env::args().nth(1).map(|arg| arg.parse().expect("the master seed must be a u64")).unwrap_or_else(rand::random);
+    println!("master seed = {}", master_seed);
+    // benchmark::vanilla::run_benchmark_speed(master_seed);
+    // benchmark::vanilla::run_benchmark_accuracy(master_seed);
+    benchmark::hllpp::benchmarK_accuracy_hll(master_seed);
+    // benchmark::hllpp::benchmarK_accuracy_hllpp(master_seed);
+    // benchmark::hllpp::benchmarK_accuracy_hllpprh(master_seed);
+    // benchmark::hllpp::benchmark_accuracy_exaloglog(master_seed);
+    // benchmark::loglog::run_benchmark_speed_loglog(master_seed);
+    // benchmark::loglog::run_benchmark_speed_superloglog(master_seed);
+    // benchmark::loglog::benchmark_accuracy_loglog(master_seed);
+    // benchmark::loglog::benchmark_accuracy_superloglog(master_seed);
+    // benchmark::pcsa::run_benchmark_speed_pcsa(master_seed);
+    // benchmark::pcsa::benchmark_accuracy_pcsa(master_seed);
+    // benchmark::linearcounting::run_benchmark_speed_linearcounting(master_seed);
+    // benchmark::linearcounting::benchmark_accuracy_linearcounting(master_seed);
+    // benchmark::kmv::run_benchmark_speed_kmv(master_seed);
+    // benchmark::kmv::benchmark_accuracy_kmv(master_seed);
+    // benchmark::kmv::benchmark_accuracy_kmv_intersection(master_seed);
+    // benchmark::ultraloglog::run_benchmark_speed_ultraloglog(master_seed);
+    // benchmark::ultraloglog::benchmark_accuracy_ultraloglog(master_seed);
+    // benchmark::ultraloglog::benchmark_accuracy_ultraloglog_ml(master_seed);
+    // benchmark::cpc::run_benchmark_speed_cpc(master_seed);
+    // benchmark::cpc::benchmark_accuracy_cpc(master_seed);
+    // benchmark::cpc::benchmark_size_cpc_hllpp(master_seed);
+    // benchmark::theta::benchmark_accuracy_theta_sketch(master_seed);
+    // benchmark::hyperlogloglog::run_benchmark_speed_hyperlogloglog(master_seed);
+    // benchmark::cvm::run_benchmark_speed_cvm(master_seed);
+    // benchmark::cvm::benchmark_accuracy_cvm(master_seed);
+    // benchmark::cvm::benchmark_accuracy_cvm_structured_inputs(master_seed);
+    // benchmark::setsketch::benchmark_accuracy_setsketch(master_seed);
+    // benchmark::setsketch::run_benchmark_speed_setsketch(master_seed);
+    // benchmark::setsketch::benchmark_accuracy_setsketch_intersection(master_seed);
+    // benchmark::hllpp::benchmark_accuracy_hyperminhash_intersection(master_seed);
+    // benchmark::vanilla::benchmark_accuracy_sliding_hll(master_seed);
+    // benchmark::rollup::benchmark_accuracy_rollup(master_seed);
+    // benchmark::hllpp::benchmark_size_hllpp_registry(master_seed);
+    // benchmark::vanilla::benchmark_accuracy_vhll(master_seed);
+    // benchmark::hllpp::benchmark_accuracy_with_hasher::<universalhash::univesalhash::UniversalHashFunction>("multiply-shift", master_seed);
+    // benchmark::hllpp::run_benchmark_speed_with_hasher::<universalhash::univesalhash::UniversalHashFunction>("multiply-shift", master_seed);
+    // benchmark::hllpp::benchmark_accuracy_hash_families(master_seed);
+    // benchmark::hllpp::run_benchmark_speed_hash_families(master_seed);
+    // benchmark::hllpp::benchmark_accuracy_polynomial_independence(master_seed);
+    // benchmark::adversarial::benchmark_adversarial_hash_families(master_seed);
+    // benchmark::hllpp::benchmark_accuracy_string_keys(master_seed);
 }
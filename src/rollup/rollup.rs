@@ -0,0 +1,245 @@
+use crate::hllpp::hllpp::HLLPP;
+use crate::universalhash::univesalhash::*;
+use rand::prelude::*;
+use std::collections::BTreeMap;
+
+// one bucket width and how long buckets of that width are kept before they get compacted
+// into the next (coarser) resolution, or dropped if there is none
+pub struct Resolution {
+    pub width: u64,
+    pub retention: u64,
+}
+
+// time series of HLLPPs, one per time bucket, finest resolution first (e.g. minutes, then hours, then days)
+// every sketch shares one hash function so any set of buckets can be merged into a single range estimate
+pub struct TimeBucketedHLLPP<const Precision: usize, const SparsePrecision: usize> {
+    resolutions: Vec<Resolution>,
+    // bucket start -> sketch, per resolution
+    levels: Vec<BTreeMap<u64, HLLPP<Precision, SparsePrecision>>>,
+    // everything before this has already been moved out of the resolution
+    compacted_before: Vec<u64>,
+    latest_timestamp: u64,
+    hasher: UniversalHashFunction,
+    seed: Option<u64>,
+}
+
+impl<const Precision: usize, const SparsePrecision: usize> TimeBucketedHLLPP<Precision, SparsePrecision> {
+    const MINUTE: u64 = 60;
+    const HOUR: u64 = 60 * Self::MINUTE;
+    const DAY: u64 = 24 * Self::HOUR;
+
+    // timestamps in seconds, minutes for 2 hours, hours for 2 days and days for a year
+    pub fn new() -> Self {
+        Self::with_seed(rand::rng().random())
+    }
+
+    pub fn with_seed(seed: u64) -> Self {
+        let mut rollup = Self::with_resolutions(
+            vec![
+                Resolution { width: Self::MINUTE, retention: 2 * Self::HOUR },
+                Resolution { width: Self::HOUR, retention: 2 * Self::DAY },
+                Resolution { width: Self::DAY, retention: 365 * Self::DAY },
+            ],
+            UniversalHashFunctionsFamily::with_seed(64, seed).construct_new_hash_function_with_random_seeds(),
+        );
+        rollup.seed = Some(seed);
+        rollup
+    }
+
+    pub fn with_resolutions(resolutions: Vec<Resolution>, hasher: UniversalHashFunction) -> Self {
+        assert!(!resolutions.is_empty(), "time bucketed hllpp needs at least one resolution");
+        for (resolution, coarser_resolution) in resolutions.iter().zip(resolutions.iter().skip(1)) {
+            assert!(resolution.width > 0 && coarser_resolution.width % resolution.width == 0, "every bucket width must divide the next one");
+        }
+        let num_resolutions = resolutions.len();
+        Self {
+            resolutions,
+            levels: (0..num_resolutions).map(|_| BTreeMap::new()).collect(),
+            compacted_before: vec![0; num_resolutions],
+            latest_timestamp: 0,
+            hasher,
+            seed: None,
+        }
+    }
+
+    pub fn seed(&self) -> Option<u64> {
+        self.seed
+    }
+
+    // late data goes into the finest resolution that still covers its timestamp, data older than everything kept is ignored
+    pub fn read_data(&mut self, data: u64, timestamp: u64) {
+        if let Some(level_idx) = (0..self.levels.len()).find(|&level_idx| timestamp >= self.compacted_before[level_idx]) {
+            let bucket_start = timestamp - timestamp % self.resolutions[level_idx].width;
+            let hasher = &self.hasher;
+            self.levels[level_idx].entry(bucket_start)
+                .or_insert_with(|| HLLPP::with_hash_function(hasher.clone()))
+                .read_data(data);
+        }
+        if timestamp > self.latest_timestamp {
+            self.latest_timestamp = timestamp;
+            self.compact();
+        }
+    }
+
+    // distinct items with a timestamp in start..end
+    // compacted buckets only partly inside the range are counted whole, so the range effectively gets rounded outwards
+    pub fn compute_estimates(&self, start: u64, end: u64) -> f64 {
+        self.merge_range(start, end).compute_estimates()
+    }
+
+    // an empty or reversed range merges nothing
+    pub fn merge_range(&self, start: u64, end: u64) -> HLLPP<Precision, SparsePrecision> {
+        let mut merged = HLLPP::with_hash_function(self.hasher.clone());
+        if start >= end {
+            return merged;
+        }
+        for (level, resolution) in self.levels.iter().zip(self.resolutions.iter()) {
+            // a bucket starting before start can still overlap the range
+            let first_bucket_start = start - start % resolution.width;
+            for (_, sketch) in level.range(first_bucket_start..end) {
+                merged.merge(sketch);
+            }
+        }
+        merged
+    }
+
+    pub fn num_buckets(&self) -> usize {
+        self.levels.iter().map(|level| level.len()).sum()
+    }
+
+    pub fn size_in_bytes(&self) -> usize {
+        self.levels.iter().flat_map(|level| level.values()).map(|sketch| sketch.size_in_bytes()).sum()
+    }
+
+    fn compact(&mut self) {
+        for level_idx in 0..self.levels.len() {
+            // move whole coarser buckets at once so the coarser resolution never receives part of a bucket late
+            let alignment = self.resolutions.get(level_idx + 1).unwrap_or(&self.resolutions[level_idx]).width;
+            let oldest_kept = self.latest_timestamp.saturating_sub(self.resolutions[level_idx].retention);
+            let boundary = oldest_kept - oldest_kept % alignment;
+            if boundary <= self.compacted_before[level_idx] {
+                continue;
+            }
+            self.compacted_before[level_idx] = boundary;
+            let kept = self.levels[level_idx].split_off(&boundary);
+            let expired = std::mem::replace(&mut self.levels[level_idx], kept);
+            if let Some(coarser_resolution) = self.resolutions.get(level_idx + 1) {
+                let coarser_width = coarser_resolution.width;
+                for (bucket_start, sketch) in expired {
+                    let hasher = &self.hasher;
+                    self.levels[level_idx + 1].entry(bucket_start - bucket_start % coarser_width)
+                        .or_insert_with(|| HLLPP::with_hash_function(hasher.clone()))
+                        .merge(&sketch);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::*;
+
+    const NUM_ITEMS_PER_TIMESTAMP: usize = 100;
+
+    type TestRollup = TimeBucketedHLLPP<14, 25>;
+
+    // buckets of 1 kept for 10, of 10 kept for 100 and of 100 kept for 1000
+    fn rollup() -> TestRollup {
+        TestRollup::with_resolutions(
+            vec![
+                Resolution { width: 1, retention: 10 },
+                Resolution { width: 10, retention: 100 },
+                Resolution { width: 100, retention: 1000 },
+            ],
+            hash_function(2),
+        )
+    }
+
+    // NUM_ITEMS_PER_TIMESTAMP new items at every timestamp in timestamps, in order
+    fn timestamped_items(seed: u64, timestamps: std::ops::Range<u64>) -> Vec<(u64, u64)> {
+        let items = random_items(seed, (timestamps.end - timestamps.start) as usize * NUM_ITEMS_PER_TIMESTAMP);
+        items.into_iter().enumerate().map(|(i, data)| (data, timestamps.start + (i / NUM_ITEMS_PER_TIMESTAMP) as u64)).collect()
+    }
+
+    fn estimates_of(items: impl Iterator<Item = u64>) -> f64 {
+        let mut hllpp = HLLPP::<14, 25>::with_hash_function(hash_function(2));
+        for data in items {
+            hllpp.read_data(data);
+        }
+        hllpp.compute_estimates()
+    }
+
+    #[test]
+    fn ranges_equal_a_sketch_of_their_items() {
+        // some buckets get compacted into buckets of 10, ranges aligned to those still cover exactly their items
+        let (mut rollup, items) = (rollup(), timestamped_items(1, 0..50));
+        for &(data, timestamp) in &items {
+            rollup.read_data(data, timestamp);
+        }
+        for (start, end) in [(0, 50), (10, 20), (49, 1000)] {
+            let range_items = items.iter().filter(|&&(_, timestamp)| start <= timestamp && timestamp < end).map(|&(data, _)| data);
+            assert_eq!(rollup.merge_range(start, end).compute_estimates(), estimates_of(range_items), "range {}..{}", start, end);
+        }
+    }
+
+    #[test]
+    fn compaction_keeps_range_estimates() {
+        let (mut rollup, items) = (rollup(), timestamped_items(3, 0..100));
+        for &(data, timestamp) in &items {
+            rollup.read_data(data, timestamp);
+        }
+        let (whole_range, second_half) = (rollup.compute_estimates(0, 100), rollup.compute_estimates(50, 100));
+        assert_eq!(whole_range, estimates_of(items.iter().map(|&(data, _)| data)));
+        // at 250 the buckets before 240 leave the finest resolution and the ones before 100 end up in one bucket of 100
+        rollup.read_data(random_items(4, 1)[0], 250);
+        assert_eq!(rollup.levels[0].keys().copied().collect::<Vec<_>>(), vec![250]);
+        assert!(rollup.levels[1].is_empty());
+        assert_eq!(rollup.levels[2].keys().copied().collect::<Vec<_>>(), vec![0]);
+        assert_eq!(rollup.compute_estimates(0, 100), whole_range);
+        // the range gets rounded out to the bucket of 100 it now falls into
+        assert!(rollup.compute_estimates(50, 100) > second_half);
+        assert_eq!(rollup.compute_estimates(50, 100), whole_range);
+    }
+
+    #[test]
+    fn late_data_goes_to_the_finest_resolution_covering_it() {
+        // at 249 everything before 230 is out of the finest resolution and everything before 100 out of the middle one
+        let mut rollup = rollup();
+        rollup.read_data(random_items(5, 1)[0], 249);
+        for (data, timestamp) in random_items(6, 3).into_iter().zip([5, 155, 245]) {
+            rollup.read_data(data, timestamp);
+        }
+        assert_eq!(rollup.levels[0].keys().copied().collect::<Vec<_>>(), vec![245, 249]);
+        assert_eq!(rollup.levels[1].keys().copied().collect::<Vec<_>>(), vec![150]);
+        assert_eq!(rollup.levels[2].keys().copied().collect::<Vec<_>>(), vec![0]);
+    }
+
+    #[test]
+    fn data_past_the_last_retention_is_dropped() {
+        let mut rollup = rollup();
+        for (data, timestamp) in timestamped_items(7, 0..300) {
+            rollup.read_data(data, timestamp);
+        }
+        let num_buckets = rollup.num_buckets();
+        // at 2000 every bucket so far is older than the 1000 kept by the coarsest resolution
+        rollup.read_data(random_items(8, 1)[0], 2000);
+        assert!(rollup.num_buckets() < num_buckets);
+        assert_eq!(rollup.num_buckets(), 1);
+        assert_eq!(rollup.compute_estimates(0, 1000), 0f64);
+        // and late data from back then is ignored
+        rollup.read_data(random_items(9, 1)[0], 0);
+        assert_eq!(rollup.num_buckets(), 1);
+    }
+
+    #[test]
+    fn reversed_range_estimates_0() {
+        let mut rollup = rollup();
+        for (data, timestamp) in timestamped_items(10, 0..50) {
+            rollup.read_data(data, timestamp);
+        }
+        assert_eq!(rollup.compute_estimates(40, 20), 0f64);
+        assert_eq!(rollup.compute_estimates(20, 20), 0f64);
+    }
+}
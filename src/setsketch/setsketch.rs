@@ -0,0 +1,244 @@
+use crate::universalhash::univesalhash::*;
+use rand::prelude::*;
+
+// ertl's setsketch (2021), setsketch1 variant
+// every item draws one exponential(a) value per register (from a prng seeded with its hash),
+// register i keeps max(floor(1 - log_b(min value seen))) clamped to 0..=q + 1
+// b -> 1 behaves like minhash (good similarity estimates), b = 2 behaves like hll (small registers)
+// the values are generated in increasing order over a random permutation of the registers,
+// so an insert can stop as soon as it cannot beat the smallest register anymore
+pub struct SetSketch {
+    base: f64,
+    rate: f64,
+    max_register: u16,
+    registers: Vec<u16>,
+    min_register: u16,
+    num_at_min_register: usize,
+    // lazily reset fisher-yates permutation, entries whose version is stale are the identity
+    permutation: Vec<u32>,
+    permutation_versions: Vec<u32>,
+    permutation_version: u32,
+    hash_function: UniversalHashFunction,
+    seed: Option<u64>,
+}
+
+impl SetSketch {
+    // 16 bit registers with the parameters recommended in the paper
+    const DEFAULT_BASE: f64 = 1.001;
+    const DEFAULT_RATE: f64 = 20f64;
+    const DEFAULT_MAX_REGISTER: u16 = u16::MAX - 1;
+
+    pub fn new(num_registers: usize) -> Self {
+        Self::with_seed(num_registers, rand::rng().random())
+    }
+
+    pub fn with_seed(num_registers: usize, seed: u64) -> Self {
+        let mut setsketch = Self::with_hash_function(num_registers, UniversalHashFunctionsFamily::with_seed(64, seed).construct_new_hash_function_with_random_seeds());
+        setsketch.seed = Some(seed);
+        setsketch
+    }
+
+    pub fn with_hash_function(num_registers: usize, hash_function: UniversalHashFunction) -> Self {
+        Self::with_parameters(num_registers, Self::DEFAULT_BASE, Self::DEFAULT_RATE, Self::DEFAULT_MAX_REGISTER, hash_function)
+    }
+
+    pub fn with_parameters(num_registers: usize, base: f64, rate: f64, max_register: u16, hash_function: UniversalHashFunction) -> Self {
+        assert!(num_registers > 0 && num_registers <= u32::MAX as usize, "setsketch needs 1 to 2^32 - 1 registers");
+        assert!(base > 1f64, "setsketch needs a base > 1");
+        assert!(rate > 0f64, "setsketch needs a rate > 0");
+        assert!(max_register < u16::MAX, "setsketch needs max_register + 1 to fit in a register");
+        Self {
+            base,
+            rate,
+            max_register,
+            registers: vec![0; num_registers],
+            min_register: 0,
+            num_at_min_register: num_registers,
+            permutation: vec![0; num_registers],
+            permutation_versions: vec![0; num_registers],
+            permutation_version: 0,
+            hash_function,
+            seed: None,
+        }
+    }
+
+    pub fn seed(&self) -> Option<u64> {
+        self.seed
+    }
+
+    pub fn read_data(&mut self, data: u64) {
+        let mut rng_state = self.hash_function.hash64(data);
+        self.next_permutation_version();
+        let num_registers = self.registers.len();
+        let mut point = 0f64;
+        for permutation_idx in 0..num_registers {
+            // the permutation_idx-th smallest of num_registers exponential(rate) values
+            let exponential = -f64::ln(Self::next_uniform(&mut rng_state)) / self.rate;
+            point += exponential / (num_registers - permutation_idx) as f64;
+            let register = self.get_register_value(point);
+            if register <= self.min_register {
+                break;
+            }
+            let swap_idx = permutation_idx + ((Self::next_random(&mut rng_state) as u128 * (num_registers - permutation_idx) as u128) >> 64) as usize;
+            let register_idx = self.swap_permutation(permutation_idx, swap_idx);
+            if register > self.registers[register_idx] {
+                self.set_register(register_idx, register);
+            }
+        }
+    }
+
+    pub fn compute_estimates(&self) -> f64 {
+        // n = m (1 - 1/b) / (a ln(b) sum_i b^-K_i)
+        let total = self.registers.iter().map(|&register| self.base.powi(-(register as i32))).sum::<f64>();
+        self.registers.len() as f64 * (1f64 - 1f64 / self.base) / (self.rate * self.base.ln() * total)
+    }
+
+    pub fn merge(&mut self, other: &Self) {
+        self.assert_compatible(other);
+        for (register, &other_register) in self.registers.iter_mut().zip(other.registers.iter()) {
+            *register = (*register).max(other_register);
+        }
+        self.recompute_min_register();
+    }
+
+    pub fn union(&self, other: &Self) -> Self {
+        let mut union = Self::with_parameters(self.registers.len(), self.base, self.rate, self.max_register, self.hash_function.clone());
+        union.seed = self.seed;
+        union.registers = self.registers.clone();
+        union.merge(other);
+        union
+    }
+
+    pub fn jaccard_estimates(&self, other: &Self) -> f64 {
+        // with A = U \ V, B = V \ U and n = |U u V|, register i of U is bigger than register i of V with probability
+        // p(|A| / n) = -ln(1 - (1 - 1/b) |A| / n) / ln(b), so invert that for both directions and J = 1 - |A|/n - |B|/n
+        self.assert_compatible(other);
+        let num_registers = self.registers.len() as f64;
+        let (mut num_greater, mut num_less) = (0usize, 0usize);
+        for (&register, &other_register) in self.registers.iter().zip(other.registers.iter()) {
+            if register > other_register {
+                num_greater += 1;
+            }
+            else if register < other_register {
+                num_less += 1;
+            }
+        }
+        let invert = |num_differing: usize| -> f64 {
+            (1f64 - self.base.powf(-(num_differing as f64) / num_registers)) / (1f64 - 1f64 / self.base)
+        };
+        (1f64 - invert(num_greater) - invert(num_less)).clamp(0f64, 1f64)
+    }
+
+    pub fn intersection_estimates(&self, other: &Self) -> f64 {
+        self.jaccard_estimates(other) * self.union(other).compute_estimates()
+    }
+
+    fn assert_compatible(&self, other: &Self) {
+        assert!(
+            self.registers.len() == other.registers.len() && self.base == other.base && self.rate == other.rate && self.max_register == other.max_register,
+            "cannot combine setsketches with different parameters"
+        );
+        assert!(self.hash_function == other.hash_function, "cannot combine setsketches with different hash functions");
+    }
+
+    fn get_register_value(&self, point: f64) -> u16 {
+        let register = (1f64 - point.ln() / self.base.ln()).floor();
+        register.clamp(0f64, self.max_register as f64 + 1f64) as u16
+    }
+
+    fn set_register(&mut self, register_idx: usize, register: u16) {
+        if self.registers[register_idx] == self.min_register {
+            self.num_at_min_register -= 1;
+        }
+        self.registers[register_idx] = register;
+        if self.num_at_min_register == 0 {
+            self.recompute_min_register();
+        }
+    }
+
+    fn recompute_min_register(&mut self) {
+        self.min_register = *self.registers.iter().min().unwrap();
+        self.num_at_min_register = self.registers.iter().filter(|&&register| register == self.min_register).count();
+    }
+
+    fn next_permutation_version(&mut self) {
+        self.permutation_version = self.permutation_version.wrapping_add(1);
+        if self.permutation_version == 0 {
+            self.permutation_versions.iter_mut().for_each(|version| *version = 0);
+            self.permutation_version = 1;
+        }
+    }
+
+    fn get_permutation(&self, idx: usize) -> u32 {
+        if self.permutation_versions[idx] == self.permutation_version {
+            self.permutation[idx]
+        }
+        else {
+            idx as u32
+        }
+    }
+
+    fn swap_permutation(&mut self, idx: usize, other_idx: usize) -> usize {
+        // swaps the two entries and returns the new entry at idx
+        let (value, other_value) = (self.get_permutation(idx), self.get_permutation(other_idx));
+        self.permutation[idx] = other_value;
+        self.permutation[other_idx] = value;
+        self.permutation_versions[idx] = self.permutation_version;
+        self.permutation_versions[other_idx] = self.permutation_version;
+        other_value as usize
+    }
+
+    fn next_random(state: &mut u64) -> u64 {
+        // splitmix64, only used to expand one hash into a deterministic stream per item
+        *state = state.wrapping_add(0x9E3779B97F4A7C15);
+        let mut z = *state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        z ^ (z >> 31)
+    }
+
+    fn next_uniform(state: &mut u64) -> f64 {
+        // uniform in (0, 1]
+        ((Self::next_random(state) >> 11) + 1) as f64 / 2f64.powi(53)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::*;
+
+    fn setsketch_of(items: &[u64]) -> SetSketch {
+        let mut setsketch = SetSketch::with_hash_function(4096, hash_function(1));
+        items.iter().for_each(|&data| setsketch.read_data(data));
+        setsketch
+    }
+
+    #[test]
+    fn estimates_are_accurate() {
+        // standard error is about 1 / sqrt(m) = 1.6%
+        for num_items in [100, 10_000, 200_000] {
+            let estimates = setsketch_of(&random_items(2, num_items)).compute_estimates();
+            assert!((estimates - num_items as f64).abs() < 0.06 * num_items as f64, "{} items, estimate {}", num_items, estimates);
+        }
+    }
+
+    #[test]
+    fn merge_equals_inserting_everything() {
+        let (a_items, b_items) = overlapping_items(3);
+        let (mut merged, other, single) = (setsketch_of(&a_items), setsketch_of(&b_items), setsketch_of(&[a_items, b_items].concat()));
+        assert_eq!(merged.union(&other).registers, single.registers);
+        merged.merge(&other);
+        assert_eq!(merged.registers, single.registers);
+        assert_eq!((merged.min_register, merged.num_at_min_register), (single.min_register, single.num_at_min_register));
+    }
+
+    #[test]
+    fn set_operations_on_known_overlap() {
+        let (a_items, b_items) = overlapping_items(4);
+        let (a, b) = (setsketch_of(&a_items), setsketch_of(&b_items));
+        assert!((a.jaccard_estimates(&b) - 0.2).abs() < 0.03, "jaccard {}", a.jaccard_estimates(&b));
+        assert!((a.intersection_estimates(&b) - 20_000f64).abs() < 0.15 * 20_000f64, "intersection {}", a.intersection_estimates(&b));
+        assert_eq!(a.jaccard_estimates(&a), 1f64);
+    }
+}
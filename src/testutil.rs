@@ -0,0 +1,23 @@
+use crate::universalhash::univesalhash::*;
+use rand::prelude::*;
+use rand::rngs::StdRng;
+
+// inputs shared by the estimators' unit tests
+
+pub(crate) fn random_items(seed: u64, num_items: usize) -> Vec<u64> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    (0..num_items).map(|_| rng.random()).collect()
+}
+
+// items 0..60k and 40k..100k of one random stream, so the union has 100k items,
+// the intersection 20k and the jaccard index is 0.2
+pub(crate) fn overlapping_items(seed: u64) -> (Vec<u64>, Vec<u64>) {
+    let items = random_items(seed, 100_000);
+    (items[..60_000].to_vec(), items[40_000..].to_vec())
+}
+
+// one fixed hash function per seed, so sketches built with the same seed can be merged and compared
+pub(crate) fn hash_function(seed: u64) -> UniversalHashFunction {
+    let mut rng = StdRng::seed_from_u64(seed);
+    UniversalHashFunctionsFamily::new(64).construct_new_hash_function(rng.random(), rng.random())
+}
@@ -0,0 +1,143 @@
+use crate::universalhash::univesalhash::*;
+use rand::prelude::*;
+use std::collections::BTreeSet;
+
+// theta sketch (dasgupta et al. 2016), the kmv generalization behind apache datasketches' set expressions
+// keeps every hash below a threshold theta (scaled to u64, u64::MAX means theta = 1 i.e. exact mode)
+// and at most k of them, lowering theta whenever it has to drop one
+// union, intersection and a-not-b all return theta sketches again, so set expressions can be nested arbitrarily,
+// e.g. a.union(&b).intersection(&c.a_not_b(&d))
+pub struct ThetaSketch {
+    k: usize,
+    theta: u64,
+    hashes: BTreeSet<u64>,
+    hash_function: UniversalHashFunction,
+    seed: Option<u64>,
+}
+
+impl ThetaSketch {
+    pub fn new(k: usize) -> Self {
+        Self::with_seed(k, rand::rng().random())
+    }
+
+    pub fn with_seed(k: usize, seed: u64) -> Self {
+        let mut theta = Self::with_hash_function(k, UniversalHashFunctionsFamily::with_seed(64, seed).construct_new_hash_function_with_random_seeds());
+        theta.seed = Some(seed);
+        theta
+    }
+
+    pub fn with_hash_function(k: usize, hash_function: UniversalHashFunction) -> Self {
+        assert!(k > 0, "theta sketch needs k > 0");
+        Self {
+            k,
+            theta: u64::MAX,
+            hashes: BTreeSet::new(),
+            hash_function,
+            seed: None,
+        }
+    }
+
+    pub fn seed(&self) -> Option<u64> {
+        self.seed
+    }
+
+    pub fn read_data(&mut self, data: u64) {
+        let hashed_data = self.hash_function.hash64(data);
+        if hashed_data < self.theta && self.hashes.insert(hashed_data) {
+            self.reduce_to_k();
+        }
+    }
+
+    pub fn compute_estimates(&self) -> f64 {
+        if self.theta == u64::MAX {
+            return self.hashes.len() as f64;
+        }
+        self.hashes.len() as f64 / self.get_theta_f64()
+    }
+
+    pub fn union(&self, other: &Self) -> Self {
+        let mut union = self.with_same_hash_function(other, self.k.min(other.k));
+        union.theta = self.theta.min(other.theta);
+        union.hashes = self.hashes.union(&other.hashes).copied().filter(|&hashed_data| hashed_data < union.theta).collect();
+        union.reduce_to_k();
+        union
+    }
+
+    pub fn intersection(&self, other: &Self) -> Self {
+        let mut intersection = self.with_same_hash_function(other, self.k.min(other.k));
+        intersection.theta = self.theta.min(other.theta);
+        intersection.hashes = self.hashes.intersection(&other.hashes).copied().filter(|&hashed_data| hashed_data < intersection.theta).collect();
+        intersection
+    }
+
+    pub fn a_not_b(&self, other: &Self) -> Self {
+        let mut a_not_b = self.with_same_hash_function(other, self.k);
+        a_not_b.theta = self.theta.min(other.theta);
+        a_not_b.hashes = self.hashes.difference(&other.hashes).copied().filter(|&hashed_data| hashed_data < a_not_b.theta).collect();
+        a_not_b
+    }
+
+    fn with_same_hash_function(&self, other: &Self, k: usize) -> Self {
+        assert!(self.hash_function == other.hash_function, "cannot combine theta sketches with different hash functions");
+        Self::with_hash_function(k, self.hash_function.clone())
+    }
+
+    fn reduce_to_k(&mut self) {
+        while self.hashes.len() > self.k {
+            self.theta = self.hashes.pop_last().unwrap();
+        }
+    }
+
+    fn get_theta_f64(&self) -> f64 {
+        self.theta as f64 / 2f64.powi(64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::*;
+
+    fn theta_of(k: usize, items: &[u64]) -> ThetaSketch {
+        let mut theta = ThetaSketch::with_hash_function(k, hash_function(1));
+        items.iter().for_each(|&data| theta.read_data(data));
+        theta
+    }
+
+    fn assert_close(name: &str, estimates: f64, true_cardinality: f64, relative_error: f64) {
+        assert!((estimates - true_cardinality).abs() < relative_error * true_cardinality, "{}: estimate {}, expected {}", name, estimates, true_cardinality);
+    }
+
+    #[test]
+    fn exact_mode_below_k() {
+        let items = random_items(2, 3000);
+        let (a, b) = (theta_of(4096, &items[..2000]), theta_of(4096, &items[1000..]));
+        assert_eq!(a.union(&b).compute_estimates(), 3000f64);
+        assert_eq!(a.intersection(&b).compute_estimates(), 1000f64);
+        assert_eq!(a.a_not_b(&b).compute_estimates(), 1000f64);
+    }
+
+    #[test]
+    fn union_equals_inserting_everything() {
+        let (a_items, b_items) = overlapping_items(3);
+        let union = theta_of(4096, &a_items).union(&theta_of(4096, &b_items));
+        let single = theta_of(4096, &[a_items, b_items].concat());
+        assert_eq!(union.theta, single.theta);
+        assert_eq!(union.hashes, single.hashes);
+    }
+
+    #[test]
+    fn set_operations_on_known_overlap() {
+        // a = items 0..60k and b = items 40k..100k, c = items 50k..100k plus 50k more
+        let (a_items, b_items) = overlapping_items(4);
+        let c_items = [b_items[10_000..].to_vec(), random_items(5, 50_000)].concat();
+        let (a, b, c) = (theta_of(4096, &a_items), theta_of(4096, &b_items), theta_of(4096, &c_items));
+        // standard error is about 1 / sqrt(k) = 1.6% of the union, so relatively more for the smaller results
+        assert_close("a u b", a.union(&b).compute_estimates(), 100_000f64, 0.06);
+        assert_close("a n b", a.intersection(&b).compute_estimates(), 20_000f64, 0.15);
+        assert_close("a \\ b", a.a_not_b(&b).compute_estimates(), 40_000f64, 0.1);
+        // (a u b) n c = items 50k..100k, a \ (b u c) = items 0..40k
+        assert_close("(a u b) n c", a.union(&b).intersection(&c).compute_estimates(), 50_000f64, 0.1);
+        assert_close("a \\ (b u c)", a.a_not_b(&b.union(&c)).compute_estimates(), 40_000f64, 0.1);
+    }
+}
@@ -0,0 +1,248 @@
+use crate::universalhash::univesalhash::*;
+use rand::prelude::*;
+
+// ertl's ultraloglog (2023)
+// every register is still one byte like hllpp, but instead of a 6 bit max rank it keeps
+// 6 bits for the max update value and 2 history bits for whether the 2 update values right below it were seen
+// registers are stored as 4 * u + history, where u is the position of the highest set bit of the
+// "hash prefix" (one bit per update value seen, offset by num_bucket_bits - 1), so 0 still means empty
+pub struct UltraLogLog {
+    num_bucket_bits: usize,
+    num_buckets: usize,
+    registers: Vec<u8>,
+    hash_function: UniversalHashFunction,
+    seed: Option<u64>,
+}
+
+impl UltraLogLog {
+    // optimal fgra parameters from the paper
+    // the register contribution is ETA[history] * 2^(-TAU * update_value), normalized so that its mean is rate^(-TAU)
+    const TAU: f64 = 0.8194911375910897;
+    const ETA: [f64; 4] = [4.663135749698441, 2.137850286535751, 2.7811447941454626, 0.9824082439220764];
+    // asymptotic relative variance of fgra times num_buckets
+    const FGRA_VARIANCE: f64 = 0.6118931496978437;
+
+    pub fn new(num_bucket_bits: usize) -> Self {
+        Self::with_seed(num_bucket_bits, rand::rng().random())
+    }
+
+    pub fn with_seed(num_bucket_bits: usize, seed: u64) -> Self {
+        let mut ull = Self::with_hash_function(num_bucket_bits, UniversalHashFunctionsFamily::with_seed(64, seed).construct_new_hash_function_with_random_seeds());
+        ull.seed = Some(seed);
+        ull
+    }
+
+    pub fn with_hash_function(num_bucket_bits: usize, hash_function: UniversalHashFunction) -> Self {
+        assert!((3..=26).contains(&num_bucket_bits), "ultraloglog supports 3 to 26 bucket bits");
+        let num_buckets = 1 << num_bucket_bits;
+        Self {
+            num_bucket_bits,
+            num_buckets,
+            registers: vec![0; num_buckets],
+            hash_function,
+            seed: None,
+        }
+    }
+
+    pub fn seed(&self) -> Option<u64> {
+        self.seed
+    }
+
+    pub fn read_data(&mut self, data: u64) {
+        let hashed_data = self.hash_function.hash64(data);
+        let bucket_idx = (hashed_data >> (64 - self.num_bucket_bits)) as usize;
+        // leading zeros after the bucket bits, in 0..=(64 - num_bucket_bits)
+        let num_leading_zeros = ((hashed_data << self.num_bucket_bits) | ((1 << self.num_bucket_bits) - 1)).leading_zeros() as usize;
+        let hash_prefix = Self::unpack(self.registers[bucket_idx]) | (1 << (num_leading_zeros + self.num_bucket_bits - 1));
+        self.registers[bucket_idx] = Self::pack(hash_prefix);
+    }
+
+    pub fn compute_estimates(&self) -> f64 {
+        self.compute_fgra_estimates()
+    }
+
+    pub fn compute_fgra_estimates(&self) -> f64 {
+        // registers with update value <= 2 are missing history bits for the update values <= 0
+        // that the fgra contributions assume, so count them separately and replace their
+        // contributions with the expected ones given the rate estimated from them
+        let (mut num_empty, mut num_value_1, mut num_value_2_history_0, mut num_value_2_history_1) = (0usize, 0usize, 0usize, 0usize);
+        let mut total_contributions = 0f64;
+        for &register in &self.registers {
+            match self.get_update_value(register) {
+                0 => num_empty += 1,
+                1 => num_value_1 += 1,
+                2 if register & 2 == 0 => num_value_2_history_0 += 1,
+                2 => num_value_2_history_1 += 1,
+                update_value => total_contributions += Self::ETA[(register & 3) as usize] * 2f64.powf(-Self::TAU * update_value as f64),
+            }
+        }
+        if num_empty == self.num_buckets {
+            return 0f64;
+        }
+        if num_empty + num_value_1 + num_value_2_history_0 + num_value_2_history_1 > 0 {
+            let z = Self::estimate_small_range_z(self.num_buckets, num_empty, num_value_1, num_value_2_history_0, num_value_2_history_1);
+            total_contributions += self.num_buckets as f64 * Self::small_range_contributions(z);
+        }
+        let num_buckets_f64 = self.num_buckets as f64;
+        let raw_estimates = num_buckets_f64 * (num_buckets_f64 / total_contributions).powf(1f64 / Self::TAU);
+        // second order bias correction of the power
+        raw_estimates / (1f64 + (1f64 + Self::TAU) * Self::FGRA_VARIANCE / (2f64 * num_buckets_f64))
+    }
+
+    pub fn compute_ml_estimates(&self) -> f64 {
+        // poisson model: update value k arrives at rate lambda * rho(k) in every register
+        // with rho(k) = 2^-k, except the last update value which also takes the tail 2^-(max_update_value - 1)
+        // log likelihood = -lambda * alpha + sum_k beta[k] * ln(1 - exp(-lambda * rho(k)))
+        let max_update_value = 65 - self.num_bucket_bits;
+        let mut alpha = 0f64;
+        let mut beta = vec![0usize; max_update_value + 1];
+        for &register in &self.registers {
+            let update_value = self.get_update_value(register);
+            if update_value == 0 {
+                alpha += 1f64;
+                continue;
+            }
+            beta[update_value] += 1;
+            if update_value < max_update_value {
+                alpha += 2f64.powi(-(update_value as i32));
+            }
+            for (history_update_value, was_seen) in [(update_value - 1, register & 2 != 0), (update_value.wrapping_sub(2), register & 1 != 0)] {
+                if history_update_value == 0 || history_update_value > max_update_value {
+                    continue;
+                }
+                if was_seen {
+                    beta[history_update_value] += 1;
+                }
+                else {
+                    alpha += Self::rho(history_update_value, max_update_value);
+                }
+            }
+        }
+        if beta.iter().all(|&count| count == 0) {
+            return 0f64;
+        }
+        if alpha == 0f64 {
+            return f64::INFINITY;
+        }
+        // the derivative of the log likelihood is strictly decreasing in lambda, so bisect on ln(lambda)
+        let derivative = |lambda: f64| -> f64 {
+            beta.iter().enumerate()
+                .filter(|(_update_value, &count)| count > 0)
+                .map(|(update_value, &count)| {
+                    let rate = Self::rho(update_value, max_update_value);
+                    count as f64 * rate / f64::exp_m1(lambda * rate)
+                })
+                .sum::<f64>() - alpha
+        };
+        let (mut low, mut high) = (-50f64, 50f64);
+        for _i in 0..200 {
+            let mid = (low + high) / 2f64;
+            if derivative(mid.exp()) > 0f64 {
+                low = mid;
+            }
+            else {
+                high = mid;
+            }
+        }
+        self.num_buckets as f64 * ((low + high) / 2f64).exp()
+    }
+
+    pub fn merge(&mut self, other: &Self) {
+        assert_eq!(self.num_bucket_bits, other.num_bucket_bits, "cannot merge ultraloglogs with different number of buckets");
+        assert!(self.hash_function == other.hash_function, "cannot merge ultraloglogs with different hash functions");
+        for (register, &other_register) in self.registers.iter_mut().zip(other.registers.iter()) {
+            *register = Self::pack(Self::unpack(*register) | Self::unpack(other_register));
+        }
+    }
+
+    fn get_update_value(&self, register: u8) -> usize {
+        // 0 for empty registers, otherwise the max (leading zeros + 1) seen
+        if register == 0 {
+            0
+        }
+        else {
+            (register >> 2) as usize + 2 - self.num_bucket_bits
+        }
+    }
+
+    fn rho(update_value: usize, max_update_value: usize) -> f64 {
+        2f64.powi(-(update_value.min(max_update_value - 1) as i32))
+    }
+
+    fn unpack(register: u8) -> u64 {
+        if register == 0 {
+            return 0;
+        }
+        (4 | (register as u64 & 3)) << ((register >> 2) - 2)
+    }
+
+    fn pack(hash_prefix: u64) -> u8 {
+        if hash_prefix == 0 {
+            return 0;
+        }
+        let highest_bit = 63 - hash_prefix.leading_zeros();
+        ((highest_bit << 2) as u8) | ((hash_prefix >> (highest_bit - 2)) & 3) as u8
+    }
+
+    fn estimate_small_range_z(num_buckets: usize, num_empty: usize, num_value_1: usize, num_value_2_history_0: usize, num_value_2_history_1: usize) -> f64 {
+        // maximum likelihood estimate of z = exp(-lambda / 4) using only the registers with update value <= 2
+        // (every other register just contributes P(update value >= 3) = 1 - z), which is a quadratic in z
+        let alpha = (num_buckets + 3 * (num_empty + num_value_1 + num_value_2_history_0 + num_value_2_history_1)) as f64;
+        let beta = (num_buckets - num_empty - num_value_1) as f64;
+        let gamma = (4 * num_empty + 2 * num_value_1 + 3 * num_value_2_history_0 + num_value_2_history_1) as f64;
+        (f64::sqrt(beta * beta + 4f64 * alpha * gamma) - beta) / (2f64 * alpha)
+    }
+
+    fn small_range_contributions(z: f64) -> f64 {
+        // expected fgra contribution of a register with update value <= 2 (including the update values <= 0
+        // that an untruncated hash would have), per register, given z = exp(-lambda / 4)
+        // update value 2 - i has probability of being the max (1 - z^(2^i)) * z^(2^i)
+        let mut total = 0f64;
+        let mut z_power = z;
+        let mut scale = 2f64.powf(-2f64 * Self::TAU);
+        while z_power > 0f64 && z_power < 1f64 {
+            let (z_power_2, z_power_4) = (z_power * z_power, z_power * z_power * z_power * z_power);
+            let history_contributions = Self::ETA[0] * z_power_2 * z_power_4
+                + Self::ETA[1] * z_power_2 * (1f64 - z_power_4)
+                + Self::ETA[2] * (1f64 - z_power_2) * z_power_4
+                + Self::ETA[3] * (1f64 - z_power_2) * (1f64 - z_power_4);
+            total += scale * (1f64 - z_power) * z_power * history_contributions;
+            z_power = z_power_2;
+            scale *= 2f64.powf(Self::TAU);
+        }
+        total
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::*;
+
+    fn ull_of(num_bucket_bits: usize, items: &[u64]) -> UltraLogLog {
+        let mut ull = UltraLogLog::with_hash_function(num_bucket_bits, hash_function(1));
+        items.iter().for_each(|&data| ull.read_data(data));
+        ull
+    }
+
+    #[test]
+    fn estimates_are_accurate() {
+        // standard error is sqrt(0.61 / 2^12) = 1.2% for fgra and a bit lower for ml
+        for num_items in [100, 5000, 1_000_000] {
+            let ull = ull_of(12, &random_items(2, num_items));
+            for estimates in [ull.compute_fgra_estimates(), ull.compute_ml_estimates()] {
+                assert!((estimates - num_items as f64).abs() < 0.05 * num_items as f64 + 1f64, "{} items, estimate {}", num_items, estimates);
+            }
+        }
+    }
+
+    #[test]
+    fn merge_equals_inserting_everything() {
+        let (a_items, b_items) = overlapping_items(3);
+        let mut merged = ull_of(10, &a_items);
+        merged.merge(&ull_of(10, &b_items));
+        let single = ull_of(10, &[a_items, b_items].concat());
+        assert_eq!(merged.registers, single.registers);
+        assert_eq!(merged.compute_estimates(), single.compute_estimates());
+    }
+}
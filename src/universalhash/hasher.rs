@@ -0,0 +1,47 @@
+use rand::prelude::*;
+use crate::universalhash::univesalhash::*;
+
+// what a sketch needs from a hash function, so sketches can be generic over the hash family
+// the output is always 64 bits, the same seed always gives the same function, and the parameters
+// can be stored next to a serialized sketch and read back into the exact same function
+pub trait SketchHasher: Clone + PartialEq {
+    fn hash64(&self, val: u64) -> u64;
+
+    fn from_seed(seed: u64) -> Self;
+
+    fn with_random_seed() -> Self {
+        Self::from_seed(rand::rng().random())
+    }
+
+    fn parameters(&self) -> Vec<u8>;
+
+    fn from_parameters(bytes: &[u8]) -> Option<Self>;
+}
+
+impl SketchHasher for UniversalHashFunction {
+    fn hash64(&self, val: u64) -> u64 {
+        UniversalHashFunction::hash64(self, val)
+    }
+
+    fn from_seed(seed: u64) -> Self {
+        UniversalHashFunctionsFamily::with_seed(64, seed).construct_new_hash_function_with_random_seeds()
+    }
+
+    // num bits, then both seeds as little endian u128s
+    fn parameters(&self) -> Vec<u8> {
+        let (hash_results_num_bits, seed_a, seed_b) = self.seeds();
+        let mut bytes = vec![hash_results_num_bits as u8];
+        bytes.extend_from_slice(&seed_a.to_le_bytes());
+        bytes.extend_from_slice(&seed_b.to_le_bytes());
+        bytes
+    }
+
+    fn from_parameters(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() != 33 || bytes[0] == 0 || bytes[0] > 64 {
+            return None;
+        }
+        let seed_a = u128::from_le_bytes(bytes[1..17].try_into().ok()?);
+        let seed_b = u128::from_le_bytes(bytes[17..33].try_into().ok()?);
+        Some(UniversalHashFunction::new(bytes[0] as usize, seed_a, seed_b))
+    }
+}
@@ -1 +1,11 @@
-pub mod  univesalhash;
\ No newline at end of file
+pub mod  univesalhash;
+pub mod hasher;
+pub mod tabulation;
+pub mod polynomial;
+pub mod murmur3;
+pub mod xxhash;
+pub mod wyhash;
+pub mod siphash;
+pub mod string;
+#[cfg(test)]
+mod quality;
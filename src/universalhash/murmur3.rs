@@ -0,0 +1,120 @@
+use crate::universalhash::hasher::SketchHasher;
+
+// murmurhash3 x64_128 (austin appleby), the variant datasketches uses
+// the 64 bit seed initializes both halves like datasketches does, seeds below 2^32 match the reference c++ too
+pub fn murmurhash3_x64_128(bytes: &[u8], seed: u64) -> (u64, u64) {
+    const C1: u64 = 0x87c37b91114253d5;
+    const C2: u64 = 0x4cf5ad432745937f;
+    let (mut h1, mut h2) = (seed, seed);
+    let mut blocks = bytes.chunks_exact(16);
+    for block in &mut blocks {
+        let k1 = u64::from_le_bytes(block[0..8].try_into().unwrap());
+        let k2 = u64::from_le_bytes(block[8..16].try_into().unwrap());
+        h1 ^= mix_k1(k1, C1, C2);
+        h1 = h1.rotate_left(27).wrapping_add(h2).wrapping_mul(5).wrapping_add(0x52dce729);
+        h2 ^= mix_k2(k2, C1, C2);
+        h2 = h2.rotate_left(31).wrapping_add(h1).wrapping_mul(5).wrapping_add(0x38495ab5);
+    }
+    let tail = blocks.remainder();
+    let (mut k1, mut k2) = (0u64, 0u64);
+    for (i, &byte) in tail.iter().enumerate() {
+        if i < 8 {
+            k1 ^= (byte as u64) << (8 * i);
+        }
+        else {
+            k2 ^= (byte as u64) << (8 * (i - 8));
+        }
+    }
+    if tail.len() > 8 {
+        h2 ^= mix_k2(k2, C1, C2);
+    }
+    if !tail.is_empty() {
+        h1 ^= mix_k1(k1, C1, C2);
+    }
+    h1 ^= bytes.len() as u64;
+    h2 ^= bytes.len() as u64;
+    h1 = h1.wrapping_add(h2);
+    h2 = h2.wrapping_add(h1);
+    h1 = fmix64(h1);
+    h2 = fmix64(h2);
+    h1 = h1.wrapping_add(h2);
+    h2 = h2.wrapping_add(h1);
+    (h1, h2)
+}
+
+fn mix_k1(k1: u64, c1: u64, c2: u64) -> u64 {
+    k1.wrapping_mul(c1).rotate_left(31).wrapping_mul(c2)
+}
+
+fn mix_k2(k2: u64, c1: u64, c2: u64) -> u64 {
+    k2.wrapping_mul(c2).rotate_left(33).wrapping_mul(c1)
+}
+
+fn fmix64(mut k: u64) -> u64 {
+    k ^= k >> 33;
+    k = k.wrapping_mul(0xff51afd7ed558ccd);
+    k ^= k >> 33;
+    k = k.wrapping_mul(0xc4ceb9fe1a85ec53);
+    k ^ (k >> 33)
+}
+
+#[derive(Clone, PartialEq)]
+pub struct MurmurHash3 {
+    seed: u64,
+}
+
+impl MurmurHash3 {
+    // datasketches' default seed
+    pub const DATASKETCHES_SEED: u64 = 9001;
+
+    pub fn new(seed: u64) -> Self {
+        MurmurHash3 { seed }
+    }
+
+    // first half of the 128 bit hash, which is the part hll-style sketches use
+    pub fn hash_bytes(&self, bytes: &[u8]) -> u64 {
+        murmurhash3_x64_128(bytes, self.seed).0
+    }
+
+    // items are hashed as their 8 little endian bytes, like a single long key in datasketches
+    pub fn hash64(&self, val: u64) -> u64 {
+        self.hash_bytes(&val.to_le_bytes())
+    }
+}
+
+impl SketchHasher for MurmurHash3 {
+    fn hash64(&self, val: u64) -> u64 {
+        MurmurHash3::hash64(self, val)
+    }
+
+    fn from_seed(seed: u64) -> Self {
+        Self::new(seed)
+    }
+
+    fn parameters(&self) -> Vec<u8> {
+        self.seed.to_le_bytes().to_vec()
+    }
+
+    fn from_parameters(bytes: &[u8]) -> Option<Self> {
+        Some(Self::new(u64::from_le_bytes(bytes.try_into().ok()?)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn murmurhash3_known_answers() {
+        assert_eq!(murmurhash3_x64_128(b"", 0), (0, 0));
+        // 2 blocks and an 11 byte tail, so both tail words get mixed in
+        assert_eq!(murmurhash3_x64_128(b"The quick brown fox jumps over the lazy dog", 0), (0xe34bbc7bbc071b6c, 0x7a433ca9c49a9347));
+    }
+
+    #[test]
+    fn hasher_uses_first_half_of_little_endian_bytes() {
+        let hasher = MurmurHash3::new(MurmurHash3::DATASKETCHES_SEED);
+        assert_eq!(hasher.hash64(42), murmurhash3_x64_128(&42u64.to_le_bytes(), 9001).0);
+        assert!(MurmurHash3::from_parameters(&hasher.parameters()) == Some(hasher));
+    }
+}
@@ -0,0 +1,151 @@
+use rand::prelude::*;
+use rand::rngs::StdRng;
+use crate::universalhash::hasher::SketchHasher;
+
+// mersenne prime 2^61 - 1, so reducing mod p is a shift, a mask and an add
+pub(crate) const MERSENNE_PRIME: u64 = (1 << 61) - 1;
+
+pub(crate) fn reduce(val: u128) -> u64 {
+    // x mod 2^61 - 1 = (x & p) + (x >> 61), once more for the carry, then at most one subtraction
+    let folded = (val & MERSENNE_PRIME as u128) + (val >> 61);
+    let folded = (folded & MERSENNE_PRIME as u128) + (folded >> 61);
+    let folded = folded as u64;
+    if folded >= MERSENNE_PRIME { folded - MERSENNE_PRIME } else { folded }
+}
+
+pub struct PolynomialHashFunctionsFamily<const K: usize> {
+    // family of k-independent hash functions, random polynomials of degree k - 1 over Z_p with p = 2^61 - 1
+    seed: u64,
+    rng: StdRng,
+}
+
+impl<const K: usize> PolynomialHashFunctionsFamily<K> {
+    pub fn new() -> Self {
+        Self::with_seed(rand::rng().random())
+    }
+
+    pub fn with_seed(seed: u64) -> Self {
+        assert!(K >= 1, "polynomial hashing needs at least one coefficient");
+        PolynomialHashFunctionsFamily { seed, rng: StdRng::seed_from_u64(seed) }
+    }
+
+    pub fn with_rng<R: Rng>(rng: &mut R) -> Self {
+        Self::with_seed(rng.random())
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn construct_new_hash_function(&self, coefficients: [u64; K]) -> PolynomialHashFunction<K> {
+        PolynomialHashFunction::new(coefficients)
+    }
+
+    pub fn construct_new_hash_function_with_random_seeds(&mut self) -> PolynomialHashFunction<K> {
+        let coefficients = std::array::from_fn(|_| self.rng.random_range(0..MERSENNE_PRIME));
+        self.construct_new_hash_function(coefficients)
+    }
+}
+
+#[derive(Clone, PartialEq)]
+pub struct PolynomialHashFunction<const K: usize> {
+    // a_0 + a_1 x + ... + a_{k-1} x^{k-1} mod p
+    coefficients: [u64; K],
+}
+
+impl<const K: usize> PolynomialHashFunction<K> {
+    pub fn new(coefficients: [u64; K]) -> Self {
+        PolynomialHashFunction { coefficients: coefficients.map(|coefficient| coefficient % MERSENNE_PRIME) }
+    }
+
+    // uniform in 0..p, only the low 61 bits are used
+    // keys are reduced mod p first, so the k-independence holds for keys below 2^61 - 1
+    pub fn hash61(&self, val: u64) -> u64 {
+        let val = reduce(val as u128);
+        // horner's rule from the highest coefficient down
+        self.coefficients.iter().rev().fold(0u64, |hashed, &coefficient| {
+            reduce(reduce(hashed as u128 * val as u128) as u128 + coefficient as u128)
+        })
+    }
+
+    // the sketches take their bucket index from the top bits, so move the 61 bits up
+    pub fn hash64(&self, val: u64) -> u64 {
+        self.hash61(val) << 3
+    }
+}
+
+impl<const K: usize> SketchHasher for PolynomialHashFunction<K> {
+    fn hash64(&self, val: u64) -> u64 {
+        PolynomialHashFunction::hash64(self, val)
+    }
+
+    fn from_seed(seed: u64) -> Self {
+        PolynomialHashFunctionsFamily::with_seed(seed).construct_new_hash_function_with_random_seeds()
+    }
+
+    // the k coefficients as little endian u64s
+    fn parameters(&self) -> Vec<u8> {
+        self.coefficients.iter().flat_map(|coefficient| coefficient.to_le_bytes()).collect()
+    }
+
+    fn from_parameters(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() != 8 * K {
+            return None;
+        }
+        let mut coefficients = [0u64; K];
+        for (coefficient, coefficient_bytes) in coefficients.iter_mut().zip(bytes.chunks_exact(8)) {
+            *coefficient = u64::from_le_bytes(coefficient_bytes.try_into().ok()?);
+            if *coefficient >= MERSENNE_PRIME {
+                return None;
+            }
+        }
+        Some(Self::new(coefficients))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const P: u128 = MERSENNE_PRIME as u128;
+    const EDGE_INPUTS: [u64; 5] = [0, 1, MERSENNE_PRIME - 1, MERSENNE_PRIME, u64::MAX];
+
+    // horner's rule with plain u128 remainders
+    fn reference_hash61(coefficients: &[u64], val: u64) -> u64 {
+        coefficients.iter().rev().fold(0u128, |hashed, &coefficient| (hashed * (val as u128 % P) + coefficient as u128) % P) as u64
+    }
+
+    #[test]
+    fn reduce_matches_remainder() {
+        for val in EDGE_INPUTS.map(|val| val as u128).into_iter().chain([(P - 1) * (P - 1), (P - 1) * (P - 1) + P - 1, 2 * P, u128::MAX]) {
+            assert_eq!(reduce(val) as u128, val % P, "{}", val);
+        }
+    }
+
+    #[test]
+    fn hash61_matches_remainder() {
+        for coefficients in [[0, 0, 0], [1, 2, 3], [MERSENNE_PRIME - 1; 3], [MERSENNE_PRIME - 1, 0, MERSENNE_PRIME - 2]] {
+            let polynomial = PolynomialHashFunction::new(coefficients);
+            for val in EDGE_INPUTS {
+                assert_eq!(polynomial.hash61(val), reference_hash61(&coefficients, val), "{:?} at {}", coefficients, val);
+            }
+        }
+    }
+
+    #[test]
+    fn from_parameters_rejects_invalid_coefficients() {
+        let polynomial = PolynomialHashFunction::<4>::from_seed(1);
+        let parameters = polynomial.parameters();
+        assert!(PolynomialHashFunction::<4>::from_parameters(&parameters) == Some(polynomial));
+        assert!(PolynomialHashFunction::<4>::from_parameters(&parameters[8..]).is_none());
+        assert!(PolynomialHashFunction::<3>::from_parameters(&parameters).is_none());
+        let out_of_range: Vec<u8> = [1, 2, MERSENNE_PRIME].iter().flat_map(|coefficient| coefficient.to_le_bytes()).collect();
+        assert!(PolynomialHashFunction::<3>::from_parameters(&out_of_range).is_none());
+    }
+
+    #[test]
+    fn from_seed_is_deterministic() {
+        assert!(PolynomialHashFunction::<4>::from_seed(2) == PolynomialHashFunction::<4>::from_seed(2));
+        assert!(PolynomialHashFunction::<4>::from_seed(2) != PolynomialHashFunction::<4>::from_seed(3));
+    }
+}
@@ -0,0 +1,223 @@
+// statistical quality tests for every hash family, over sequential, strided and random inputs
+// every hasher and input is seeded, so a failure is a property of the family and not bad luck of one run
+// the sketches read the bucket index from the top bits and the rank from the leading zeros right below it,
+// so those are the bits checked for uniformity; avalanche is only asserted for the families built to mix
+use rand::prelude::*;
+use rand::rngs::StdRng;
+use crate::universalhash::hasher::SketchHasher;
+use crate::universalhash::univesalhash::UniversalHashFunction;
+use crate::universalhash::tabulation::{SimpleTabulationHashFunction, TwistedTabulationHashFunction};
+use crate::universalhash::polynomial::PolynomialHashFunction;
+use crate::universalhash::murmur3::MurmurHash3;
+use crate::universalhash::xxhash::XxHash64;
+use crate::universalhash::wyhash::WyHash;
+use crate::universalhash::siphash::SipHash24;
+use crate::universalhash::string::StringHashFunction;
+
+const SEED: u64 = 42;
+const NUM_ITEMS: u64 = 1 << 17;
+const NUM_AVALANCHE_ITEMS: usize = 1000;
+const NUM_BUCKET_BITS: usize = 10;
+const MAX_RANK: usize = 12;
+
+type Hasher = (&'static str, Box<dyn Fn(u64) -> u64>);
+
+fn hasher<H: SketchHasher + 'static>(name: &'static str) -> Hasher {
+    let hash_function = H::from_seed(SEED);
+    (name, Box::new(move |val| hash_function.hash64(val)))
+}
+
+// linear over some ring, so a flipped input bit flips a fixed pattern of output bits
+fn linear_families() -> Vec<Hasher> {
+    vec![
+        hasher::<UniversalHashFunction>("multiply-shift"),
+        hasher::<PolynomialHashFunction<2>>("polynomial k = 2"),
+        hasher::<PolynomialHashFunction<4>>("polynomial k = 4"),
+        hasher::<StringHashFunction>("string + multiply-shift"),
+    ]
+}
+
+fn mixing_families() -> Vec<Hasher> {
+    vec![
+        hasher::<TwistedTabulationHashFunction>("twisted tabulation"),
+        hasher::<MurmurHash3>("murmurhash3"),
+        hasher::<XxHash64>("xxhash64"),
+        hasher::<WyHash>("wyhash"),
+        hasher::<SipHash24>("siphash-2-4"),
+        hasher::<StringHashFunction<TwistedTabulationHashFunction>>("string + twisted tabulation"),
+    ]
+}
+
+fn every_family() -> Vec<Hasher> {
+    let mut families = linear_families();
+    families.push(hasher::<SimpleTabulationHashFunction>("simple tabulation"));
+    families.extend(mixing_families());
+    families
+}
+
+fn input_patterns() -> Vec<(&'static str, Vec<u64>)> {
+    let mut rng = StdRng::seed_from_u64(SEED);
+    vec![
+        ("sequential", (0..NUM_ITEMS).collect()),
+        ("strided 2^32", (0..NUM_ITEMS).map(|i| i << 32).collect()),
+        ("random", (0..NUM_ITEMS).map(|_| rng.random()).collect()),
+    ]
+}
+
+// pearson's statistic against the expected counts
+fn chi_square(observed: &[u64], expected: &[f64]) -> f64 {
+    observed.iter().zip(expected).map(|(&observed, &expected)| (observed as f64 - expected).powi(2) / expected).sum()
+}
+
+// a chi-square with k degrees of freedom has mean k and variance 2k, allow 6 standard deviations
+// only the upper tail fails: linear families on sequential inputs give low discrepancy sequences that land far
+// below the mean, which is too even rather than biased (their problem for hll is elsewhere, see the benchmarks)
+fn chi_square_limit(degrees_of_freedom: usize) -> f64 {
+    degrees_of_freedom as f64 + 6f64 * (2f64 * degrees_of_freedom as f64).sqrt()
+}
+
+fn occupancy_chi_square(hashed: &[u64], bucket_of: impl Fn(u64) -> usize) -> f64 {
+    let mut counts = vec![0u64; 1 << NUM_BUCKET_BITS];
+    for &hashed_data in hashed {
+        counts[bucket_of(hashed_data)] += 1;
+    }
+    chi_square(&counts, &vec![hashed.len() as f64 / counts.len() as f64; counts.len()])
+}
+
+// rank as hll sees it, position of the first one bit after the bucket bits with P(rank = r) = 2^-r
+// ranks from MAX_RANK up are pooled so every expected count stays large
+fn rank_chi_square(hashed: &[u64]) -> f64 {
+    let mut counts = vec![0u64; MAX_RANK];
+    for &hashed_data in hashed {
+        let rank = ((hashed_data << NUM_BUCKET_BITS) | (1 << (NUM_BUCKET_BITS - 1))).leading_zeros() as usize + 1;
+        counts[rank.min(MAX_RANK) - 1] += 1;
+    }
+    let expected: Vec<f64> = (1..=MAX_RANK)
+        .map(|rank| hashed.len() as f64 * 0.5f64.powi(rank.min(MAX_RANK - 1) as i32))
+        .collect();
+    chi_square(&counts, &expected)
+}
+
+// flips every input bit of every item, largest |P(output bit j flips | input bit i flips) - 1/2| over all (i, j)
+fn strict_avalanche_bias(hash_function: &dyn Fn(u64) -> u64, inputs: &[u64]) -> f64 {
+    let mut flips = vec![[0u64; 64]; 64];
+    for &data in inputs {
+        let hashed_data = hash_function(data);
+        for (input_bit, input_bit_flips) in flips.iter_mut().enumerate() {
+            let diff = hashed_data ^ hash_function(data ^ (1 << input_bit));
+            for (output_bit, output_bit_flips) in input_bit_flips.iter_mut().enumerate() {
+                *output_bit_flips += diff >> output_bit & 1;
+            }
+        }
+    }
+    flips.iter().flatten().map(|&count| (count as f64 / inputs.len() as f64 - 0.5).abs()).fold(0f64, f64::max)
+}
+
+// largest |correlation| between the flips of two output bits, pooled over every flipped input bit
+fn bit_independence_correlation(hash_function: &dyn Fn(u64) -> u64, inputs: &[u64]) -> f64 {
+    let mut flips = [0u64; 64];
+    let mut joint_flips = vec![[0u64; 64]; 64];
+    for &data in inputs {
+        let hashed_data = hash_function(data);
+        for input_bit in 0..64 {
+            let mut diff = hashed_data ^ hash_function(data ^ (1 << input_bit));
+            // walk the set bits only, every pair of them flipped together
+            while diff != 0 {
+                let output_bit = diff.trailing_zeros() as usize;
+                diff &= diff - 1;
+                flips[output_bit] += 1;
+                let mut other_diff = diff;
+                while other_diff != 0 {
+                    joint_flips[output_bit][other_diff.trailing_zeros() as usize] += 1;
+                    other_diff &= other_diff - 1;
+                }
+            }
+        }
+    }
+    let num_trials = inputs.len() as f64 * 64f64;
+    let mut max_correlation = 0f64;
+    for output_bit in 0..64 {
+        for other_output_bit in (output_bit + 1)..64 {
+            let (p, q) = (flips[output_bit] as f64 / num_trials, flips[other_output_bit] as f64 / num_trials);
+            let covariance = joint_flips[output_bit][other_output_bit] as f64 / num_trials - p * q;
+            let variance = p * (1f64 - p) * q * (1f64 - q);
+            // an output bit that always or never flips is as dependent as it gets
+            let correlation = if variance > 0f64 { covariance / variance.sqrt() } else { 1f64 };
+            max_correlation = max_correlation.max(correlation.abs());
+        }
+    }
+    max_correlation
+}
+
+#[test]
+fn bucket_indices_are_uniform() {
+    let limit = chi_square_limit((1 << NUM_BUCKET_BITS) - 1);
+    for (name, hash_function) in every_family() {
+        for (pattern_name, inputs) in input_patterns() {
+            let hashed: Vec<u64> = inputs.iter().map(|&data| hash_function(data)).collect();
+            let statistic = occupancy_chi_square(&hashed, |hashed_data| (hashed_data >> (64 - NUM_BUCKET_BITS)) as usize);
+            assert!(statistic < limit, "{} on {} inputs: chi-square {} >= {}", name, pattern_name, statistic, limit);
+        }
+    }
+}
+
+#[test]
+fn bits_below_the_bucket_index_are_uniform() {
+    let limit = chi_square_limit((1 << NUM_BUCKET_BITS) - 1);
+    for (name, hash_function) in every_family() {
+        for (pattern_name, inputs) in input_patterns() {
+            let hashed: Vec<u64> = inputs.iter().map(|&data| hash_function(data)).collect();
+            let statistic = occupancy_chi_square(&hashed, |hashed_data| ((hashed_data << NUM_BUCKET_BITS) >> (64 - NUM_BUCKET_BITS)) as usize);
+            assert!(statistic < limit, "{} on {} inputs: chi-square {} >= {}", name, pattern_name, statistic, limit);
+        }
+    }
+}
+
+#[test]
+fn ranks_follow_the_geometric_law() {
+    let limit = chi_square_limit(MAX_RANK - 1);
+    for (name, hash_function) in every_family() {
+        for (pattern_name, inputs) in input_patterns() {
+            let hashed: Vec<u64> = inputs.iter().map(|&data| hash_function(data)).collect();
+            let statistic = rank_chi_square(&hashed);
+            assert!(statistic < limit, "{} on {} inputs: chi-square {} >= {}", name, pattern_name, statistic, limit);
+        }
+    }
+}
+
+#[test]
+fn mixing_families_avalanche() {
+    // with 1000 items the bias of a fair bit has a standard deviation of 0.016, the max of 4096 of them stays below 0.1
+    // tabulation only ever sees 256 values per input byte, so its estimates are coarser
+    for (name, hash_function) in mixing_families() {
+        let max_bias_limit = if name.contains("tabulation") { 0.25 } else { 0.1 };
+        for (pattern_name, inputs) in input_patterns() {
+            let max_bias = strict_avalanche_bias(&hash_function, &inputs[..NUM_AVALANCHE_ITEMS]);
+            let max_correlation = bit_independence_correlation(&hash_function, &inputs[..NUM_AVALANCHE_ITEMS]);
+            assert!(max_bias < max_bias_limit, "{} on {} inputs: avalanche bias {}", name, pattern_name, max_bias);
+            assert!(max_correlation < 0.05, "{} on {} inputs: output bit flip correlation {}", name, pattern_name, max_correlation);
+        }
+    }
+}
+
+#[test]
+fn simple_tabulation_avalanches_on_random_inputs_only() {
+    // a flipped bit changes a single table lookup, so on inputs whose other bytes repeat (sequential, strided)
+    // the same output bits flip for every item; twisting the last lookup is what fixes that
+    let (_, hash_function) = hasher::<SimpleTabulationHashFunction>("simple tabulation");
+    for (pattern_name, inputs) in input_patterns() {
+        let max_bias = strict_avalanche_bias(&hash_function, &inputs[..NUM_AVALANCHE_ITEMS]);
+        assert_eq!(max_bias < 0.25, pattern_name == "random", "simple tabulation on {} inputs: avalanche bias {}", pattern_name, max_bias);
+    }
+}
+
+#[test]
+fn linear_families_do_not_avalanche() {
+    // universality only bounds collisions, these families are uniform (see above) but do not mix
+    for (name, hash_function) in linear_families() {
+        for (pattern_name, inputs) in input_patterns() {
+            let max_bias = strict_avalanche_bias(&hash_function, &inputs[..NUM_AVALANCHE_ITEMS]);
+            assert!(max_bias > 0.4, "{} on {} inputs: avalanche bias {}, update this test if the family now mixes", name, pattern_name, max_bias);
+        }
+    }
+}
@@ -0,0 +1,126 @@
+use rand::prelude::*;
+use rand::rngs::StdRng;
+use crate::universalhash::hasher::SketchHasher;
+
+// siphash-2-4 (aumasson & bernstein 2012), a keyed prf: without the 128 bit key an attacker cannot
+// predict where an item lands, so crafted inputs cannot push the sketches' estimates up or down
+pub fn siphash24(bytes: &[u8], key0: u64, key1: u64) -> u64 {
+    let mut state = [
+        key0 ^ 0x736f6d6570736575,
+        key1 ^ 0x646f72616e646f6d,
+        key0 ^ 0x6c7967656e657261,
+        key1 ^ 0x7465646279746573,
+    ];
+    let mut words = bytes.chunks_exact(8);
+    for word in &mut words {
+        compress(&mut state, u64::from_le_bytes(word.try_into().unwrap()));
+    }
+    // the last word holds the leftover bytes and the length mod 256 in its top byte
+    let last_word = words.remainder().iter().enumerate().fold((bytes.len() as u64) << 56, |last_word, (i, &byte)| last_word | (byte as u64) << (8 * i));
+    compress(&mut state, last_word);
+    state[2] ^= 0xff;
+    for _ in 0..4 {
+        sipround(&mut state);
+    }
+    state[0] ^ state[1] ^ state[2] ^ state[3]
+}
+
+fn compress(state: &mut [u64; 4], word: u64) {
+    state[3] ^= word;
+    sipround(state);
+    sipround(state);
+    state[0] ^= word;
+}
+
+fn sipround(state: &mut [u64; 4]) {
+    let [mut v0, mut v1, mut v2, mut v3] = *state;
+    v0 = v0.wrapping_add(v1);
+    v1 = v1.rotate_left(13) ^ v0;
+    v0 = v0.rotate_left(32);
+    v2 = v2.wrapping_add(v3);
+    v3 = v3.rotate_left(16) ^ v2;
+    v0 = v0.wrapping_add(v3);
+    v3 = v3.rotate_left(21) ^ v0;
+    v2 = v2.wrapping_add(v1);
+    v1 = v1.rotate_left(17) ^ v2;
+    v2 = v2.rotate_left(32);
+    *state = [v0, v1, v2, v3];
+}
+
+#[derive(Clone, PartialEq)]
+pub struct SipHash24 {
+    key0: u64,
+    key1: u64,
+}
+
+impl SipHash24 {
+    pub fn new(key0: u64, key1: u64) -> Self {
+        SipHash24 { key0, key1 }
+    }
+
+    pub fn hash_bytes(&self, bytes: &[u8]) -> u64 {
+        siphash24(bytes, self.key0, self.key1)
+    }
+
+    // items are hashed as their 8 little endian bytes
+    pub fn hash64(&self, val: u64) -> u64 {
+        self.hash_bytes(&val.to_le_bytes())
+    }
+}
+
+impl SketchHasher for SipHash24 {
+    fn hash64(&self, val: u64) -> u64 {
+        SipHash24::hash64(self, val)
+    }
+
+    fn from_seed(seed: u64) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
+        Self::new(rng.random(), rng.random())
+    }
+
+    // a 64 bit seed would only give 64 bits of key, so draw the whole 128 bit key
+    fn with_random_seed() -> Self {
+        let mut rng = rand::rng();
+        Self::new(rng.random(), rng.random())
+    }
+
+    // both key halves as little endian u64s
+    fn parameters(&self) -> Vec<u8> {
+        [self.key0.to_le_bytes(), self.key1.to_le_bytes()].concat()
+    }
+
+    fn from_parameters(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() != 16 {
+            return None;
+        }
+        Some(Self::new(u64::from_le_bytes(bytes[..8].try_into().ok()?), u64::from_le_bytes(bytes[8..].try_into().ok()?)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // key 00 01 .. 0f from the siphash paper
+    const KEY0: u64 = 0x0706050403020100;
+    const KEY1: u64 = 0x0f0e0d0c0b0a0908;
+
+    #[test]
+    fn siphash24_known_answers() {
+        let message: Vec<u8> = (0..64).collect();
+        assert_eq!(siphash24(&message[..0], KEY0, KEY1), 0x726fdb47dd0e0e31);
+        assert_eq!(siphash24(&message[..15], KEY0, KEY1), 0xa129ca6149be45e5);
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn siphash24_matches_std() {
+        use std::hash::Hasher;
+        let message: Vec<u8> = (0..64).map(|i| (i * 37 + 11) as u8).collect();
+        for len in 0..message.len() {
+            let mut std_hasher = std::hash::SipHasher::new_with_keys(KEY0, KEY1);
+            std_hasher.write(&message[..len]);
+            assert_eq!(siphash24(&message[..len], KEY0, KEY1), std_hasher.finish(), "length {}", len);
+        }
+    }
+}
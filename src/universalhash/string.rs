@@ -0,0 +1,163 @@
+use rand::prelude::*;
+use rand::rngs::StdRng;
+use crate::universalhash::hasher::SketchHasher;
+use crate::universalhash::polynomial::{reduce, MERSENNE_PRIME};
+use crate::universalhash::univesalhash::*;
+
+pub struct StringHashFunctionsFamily {
+    // universal hashing for variable length keys (dietzfelbinger et al. 1992, thorup 2015)
+    // the key is cut into 32 bit chunks c_1..c_n that are read as the polynomial
+    // c_1 a^n + ... + c_n a + len mod p with p = 2^61 - 1 at a random point a,
+    // then the 61 bit result goes through a fixed length hash function (the finalizer) to spread it over 64 bits
+    // two different keys of at most n chunks collide in the first step with probability <= (n + 1) / p,
+    // and once the keys are collision free the output is the finalizer's, so it keeps the finalizer's guarantees:
+    // a multiply-shift finalizer makes the family universal up to the (n + 1) / p term but inherits its poor
+    // spread on structured keys, a tabulation finalizer gives output good enough to feed sketches directly
+    seed: u64,
+    rng: StdRng,
+}
+
+impl StringHashFunctionsFamily {
+    pub fn new() -> Self {
+        Self::with_seed(rand::rng().random())
+    }
+
+    pub fn with_seed(seed: u64) -> Self {
+        StringHashFunctionsFamily { seed, rng: StdRng::seed_from_u64(seed) }
+    }
+
+    pub fn with_rng<R: Rng>(rng: &mut R) -> Self {
+        Self::with_seed(rng.random())
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn construct_new_hash_function<H: SketchHasher>(&self, point: u64, finalizer: H) -> StringHashFunction<H> {
+        StringHashFunction::new(point, finalizer)
+    }
+
+    pub fn construct_new_hash_function_with_random_seeds(&mut self) -> StringHashFunction {
+        let finalizer = UniversalHashFunctionsFamily::with_rng(64, &mut self.rng).construct_new_hash_function_with_random_seeds();
+        self.construct_new_hash_function_with_finalizer(finalizer)
+    }
+
+    // random point, caller chosen finalizer
+    pub fn construct_new_hash_function_with_finalizer<H: SketchHasher>(&mut self, finalizer: H) -> StringHashFunction<H> {
+        let point = self.rng.random_range(0..MERSENNE_PRIME);
+        self.construct_new_hash_function(point, finalizer)
+    }
+}
+
+#[derive(Clone, PartialEq)]
+pub struct StringHashFunction<H: SketchHasher = UniversalHashFunction> {
+    point: u64,
+    finalizer: H,
+}
+
+impl<H: SketchHasher> StringHashFunction<H> {
+    pub fn new(point: u64, finalizer: H) -> Self {
+        StringHashFunction { point: point % MERSENNE_PRIME, finalizer }
+    }
+
+    // in 0..p, two keys of at most n chunks collide with probability <= (n + 1) / p
+    // every chunk is below 2^32 < p so distinct keys of the same length give distinct polynomials,
+    // and the length as the constant term separates keys that only differ by trailing zero bytes
+    pub fn hash61(&self, bytes: &[u8]) -> u64 {
+        let chunks = bytes.chunks(4).map(|chunk| {
+            let mut padded = [0u8; 4];
+            padded[..chunk.len()].copy_from_slice(chunk);
+            u32::from_le_bytes(padded) as u64
+        });
+        // horner's rule, the first chunk gets the highest power
+        chunks.chain(std::iter::once(bytes.len() as u64 % MERSENNE_PRIME))
+            .fold(0u64, |hashed, term| reduce(hashed as u128 * self.point as u128 + term as u128))
+    }
+
+    pub fn hash_bytes(&self, bytes: &[u8]) -> u64 {
+        self.finalizer.hash64(self.hash61(bytes))
+    }
+
+    pub fn hash_str(&self, string: &str) -> u64 {
+        self.hash_bytes(string.as_bytes())
+    }
+
+    // items are hashed as their 8 little endian bytes
+    pub fn hash64(&self, val: u64) -> u64 {
+        self.hash_bytes(&val.to_le_bytes())
+    }
+}
+
+impl<H: SketchHasher> SketchHasher for StringHashFunction<H> {
+    fn hash64(&self, val: u64) -> u64 {
+        StringHashFunction::hash64(self, val)
+    }
+
+    // the point and the finalizer's seed both come from the one seed
+    fn from_seed(seed: u64) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let finalizer = H::from_seed(rng.random());
+        StringHashFunctionsFamily::with_rng(&mut rng).construct_new_hash_function_with_finalizer(finalizer)
+    }
+
+    // the point as a little endian u64, then the finalizer's parameters
+    fn parameters(&self) -> Vec<u8> {
+        let mut bytes = self.point.to_le_bytes().to_vec();
+        bytes.extend(self.finalizer.parameters());
+        bytes
+    }
+
+    fn from_parameters(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() < 8 {
+            return None;
+        }
+        let point = u64::from_le_bytes(bytes[..8].try_into().ok()?);
+        let finalizer = H::from_parameters(&bytes[8..])?;
+        if point >= MERSENNE_PRIME {
+            return None;
+        }
+        Some(Self::new(point, finalizer))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hash61_matches_the_polynomial() {
+        // "abcdef" is the chunks 0x64636261 and 0x6665 followed by the length 6
+        let point = 0x0123456789abcdef;
+        let hash_function = StringHashFunction::new(point, UniversalHashFunction::new(64, 1, 0));
+        let expected = [0x64636261u64, 0x6665, 6].iter().fold(0u128, |hashed, &term| (hashed * point as u128 + term as u128) % MERSENNE_PRIME as u128);
+        assert_eq!(hash_function.hash61(b"abcdef") as u128, expected);
+        assert_eq!(hash_function.hash61(b""), 0);
+    }
+
+    #[test]
+    fn trailing_zero_bytes_change_the_hash() {
+        let hash_function = StringHashFunctionsFamily::with_seed(1).construct_new_hash_function_with_random_seeds();
+        let keys: [&[u8]; 5] = [b"", b"\0", b"\0\0\0\0", b"a", b"a\0"];
+        for (i, key_a) in keys.iter().enumerate() {
+            for key_b in &keys[i + 1..] {
+                assert_ne!(hash_function.hash_bytes(key_a), hash_function.hash_bytes(key_b));
+            }
+        }
+    }
+
+    #[test]
+    fn hash64_and_hash_str_hash_the_bytes() {
+        let hash_function = StringHashFunction::<UniversalHashFunction>::from_seed(7);
+        assert_eq!(hash_function.hash64(0x0807060504030201), hash_function.hash_bytes(&[1, 2, 3, 4, 5, 6, 7, 8]));
+        assert_eq!(hash_function.hash_str("hyperloglog"), hash_function.hash_bytes(b"hyperloglog"));
+    }
+
+    #[test]
+    fn parameters_round_trip() {
+        let hash_function = StringHashFunction::<UniversalHashFunction>::from_seed(3);
+        let restored = StringHashFunction::<UniversalHashFunction>::from_parameters(&hash_function.parameters()).unwrap();
+        assert!(restored == hash_function);
+        assert!(StringHashFunction::<UniversalHashFunction>::from_parameters(&hash_function.parameters()[..40]).is_none());
+    }
+}
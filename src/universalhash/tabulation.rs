@@ -0,0 +1,193 @@
+use rand::prelude::*;
+use rand::rngs::StdRng;
+use crate::universalhash::hasher::SketchHasher;
+
+pub struct TabulationHashFunctionsFamily {
+    // tabulation hashing (carter & wegman 1977, patrascu & thorup 2012)
+    // the 64 bit key is split into 8 bytes and every byte indexes its own table of random words,
+    // the hash is the xor of the looked up words
+    seed: u64,
+    rng: StdRng,
+}
+
+impl TabulationHashFunctionsFamily {
+    pub fn new() -> Self {
+        Self::with_seed(rand::rng().random())
+    }
+
+    pub fn with_seed(seed: u64) -> Self {
+        TabulationHashFunctionsFamily { seed, rng: StdRng::seed_from_u64(seed) }
+    }
+
+    pub fn with_rng<R: Rng>(rng: &mut R) -> Self {
+        Self::with_seed(rng.random())
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn construct_new_simple_hash_function(&self, seed: u64) -> SimpleTabulationHashFunction {
+        SimpleTabulationHashFunction::new(&mut StdRng::seed_from_u64(seed))
+    }
+
+    pub fn construct_new_simple_hash_function_with_random_seeds(&mut self) -> SimpleTabulationHashFunction {
+        SimpleTabulationHashFunction::new(&mut self.rng)
+    }
+
+    pub fn construct_new_twisted_hash_function(&self, seed: u64) -> TwistedTabulationHashFunction {
+        TwistedTabulationHashFunction::new(&mut StdRng::seed_from_u64(seed))
+    }
+
+    pub fn construct_new_twisted_hash_function_with_random_seeds(&mut self) -> TwistedTabulationHashFunction {
+        TwistedTabulationHashFunction::new(&mut self.rng)
+    }
+}
+
+// 3-independent, but behaves like a truly random function for min-wise hashing and linear probing
+#[derive(Clone, PartialEq)]
+pub struct SimpleTabulationHashFunction {
+    tables: Box<[[u64; 256]; 8]>,
+}
+
+impl SimpleTabulationHashFunction {
+    pub fn new<R: Rng>(rng: &mut R) -> Self {
+        let mut tables = Box::new([[0u64; 256]; 8]);
+        tables.iter_mut().flatten().for_each(|entry| *entry = rng.random());
+        SimpleTabulationHashFunction { tables }
+    }
+
+    pub fn hash64(&self, val: u64) -> u64 {
+        val.to_le_bytes().iter().zip(self.tables.iter()).fold(0u64, |hashed, (&byte, table)| hashed ^ table[byte as usize])
+    }
+}
+
+impl SketchHasher for SimpleTabulationHashFunction {
+    fn hash64(&self, val: u64) -> u64 {
+        SimpleTabulationHashFunction::hash64(self, val)
+    }
+
+    fn from_seed(seed: u64) -> Self {
+        Self::new(&mut StdRng::seed_from_u64(seed))
+    }
+
+    // every table entry as little endian u64s, table by table
+    fn parameters(&self) -> Vec<u8> {
+        self.tables.iter().flatten().flat_map(|entry| entry.to_le_bytes()).collect()
+    }
+
+    fn from_parameters(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() != 8 * 256 * 8 {
+            return None;
+        }
+        let mut tables = Box::new([[0u64; 256]; 8]);
+        for (entry, entry_bytes) in tables.iter_mut().flatten().zip(bytes.chunks_exact(8)) {
+            *entry = u64::from_le_bytes(entry_bytes.try_into().ok()?);
+        }
+        Some(SimpleTabulationHashFunction { tables })
+    }
+}
+
+// twisted tabulation: the first 7 lookups also produce a twister that is xored into the last byte
+// before its lookup, which gives chernoff-style concentration for the estimators on top of it
+// tables hold 128 bit words, the low half is the twister and the high half is the hash
+#[derive(Clone, PartialEq)]
+pub struct TwistedTabulationHashFunction {
+    tables: Box<[[u128; 256]; 8]>,
+}
+
+impl TwistedTabulationHashFunction {
+    pub fn new<R: Rng>(rng: &mut R) -> Self {
+        let mut tables = Box::new([[0u128; 256]; 8]);
+        tables.iter_mut().flatten().for_each(|entry| *entry = rng.random());
+        TwistedTabulationHashFunction { tables }
+    }
+
+    pub fn hash64(&self, val: u64) -> u64 {
+        let bytes = val.to_le_bytes();
+        let hashed = bytes[..7].iter().zip(self.tables.iter()).fold(0u128, |hashed, (&byte, table)| hashed ^ table[byte as usize]);
+        let twisted_byte = bytes[7] ^ (hashed as u8);
+        ((hashed ^ self.tables[7][twisted_byte as usize]) >> 64) as u64
+    }
+}
+
+impl SketchHasher for TwistedTabulationHashFunction {
+    fn hash64(&self, val: u64) -> u64 {
+        TwistedTabulationHashFunction::hash64(self, val)
+    }
+
+    fn from_seed(seed: u64) -> Self {
+        Self::new(&mut StdRng::seed_from_u64(seed))
+    }
+
+    // every table entry as little endian u128s, table by table
+    fn parameters(&self) -> Vec<u8> {
+        self.tables.iter().flatten().flat_map(|entry| entry.to_le_bytes()).collect()
+    }
+
+    fn from_parameters(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() != 8 * 256 * 16 {
+            return None;
+        }
+        let mut tables = Box::new([[0u128; 256]; 8]);
+        for (entry, entry_bytes) in tables.iter_mut().flatten().zip(bytes.chunks_exact(16)) {
+            *entry = u128::from_le_bytes(entry_bytes.try_into().ok()?);
+        }
+        Some(TwistedTabulationHashFunction { tables })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_seed_is_deterministic() {
+        assert!(SimpleTabulationHashFunction::from_seed(1) == SimpleTabulationHashFunction::from_seed(1));
+        assert!(SimpleTabulationHashFunction::from_seed(1) != SimpleTabulationHashFunction::from_seed(2));
+        assert!(TwistedTabulationHashFunction::from_seed(1) == TwistedTabulationHashFunction::from_seed(1));
+        assert!(TwistedTabulationHashFunction::from_seed(1) != TwistedTabulationHashFunction::from_seed(2));
+    }
+
+    #[test]
+    fn parameters_round_trip() {
+        let simple = SimpleTabulationHashFunction::from_seed(3);
+        let parameters = simple.parameters();
+        assert!(SimpleTabulationHashFunction::from_parameters(&parameters) == Some(simple));
+        assert!(SimpleTabulationHashFunction::from_parameters(&parameters[1..]).is_none());
+        assert!(SimpleTabulationHashFunction::from_parameters(&[parameters.as_slice(), &[0]].concat()).is_none());
+        let twisted = TwistedTabulationHashFunction::from_seed(3);
+        let parameters = twisted.parameters();
+        assert!(TwistedTabulationHashFunction::from_parameters(&parameters) == Some(twisted));
+        assert!(TwistedTabulationHashFunction::from_parameters(&parameters[1..]).is_none());
+        // the simple tables are half the size, so they can't be read as twisted ones
+        assert!(TwistedTabulationHashFunction::from_parameters(&SimpleTabulationHashFunction::from_seed(3).parameters()).is_none());
+    }
+
+    #[test]
+    fn simple_hash_is_xor_of_table_entries() {
+        // table i maps byte b to b shifted into byte i plus bit i, so the lookups xor back to the key with its low byte flipped
+        let mut tables = Box::new([[0u64; 256]; 8]);
+        for (i, table) in tables.iter_mut().enumerate() {
+            for (byte, entry) in table.iter_mut().enumerate() {
+                *entry = ((byte as u64) << (8 * i)) ^ (1 << i);
+            }
+        }
+        let simple = SimpleTabulationHashFunction { tables };
+        assert_eq!(simple.hash64(0x0807060504030201), 0x08070605040302fe);
+        assert_eq!(simple.hash64(0), 0xff);
+    }
+
+    #[test]
+    fn twisted_hash_twists_last_byte() {
+        // key 0x0300000000000001: only the first byte hits a non zero entry, its twister 0x05 turns the last byte 0x03 into 0x06
+        let mut tables = Box::new([[0u128; 256]; 8]);
+        tables[0][0x01] = (0xaa << 64) | 0x05;
+        tables[7][0x03] = (0xcc << 64) | 0x77;
+        tables[7][0x06] = (0xbb << 64) | 0x77;
+        let twisted = TwistedTabulationHashFunction { tables };
+        assert_eq!(twisted.hash64(0x0300000000000001), 0xaa ^ 0xbb);
+        // without a twister the last byte is looked up as is
+        assert_eq!(twisted.hash64(0x0300000000000000), 0xcc);
+    }
+}
@@ -1,14 +1,31 @@
 use rand::prelude::*;
+use rand::rngs::StdRng;
 
 pub struct UniversalHashFunctionsFamily {
-    // family of universal hash functions constructed by multiply-shift method
+    // family of universal hash functions constructed by dietzfelbinger's multiply-add-shift method
+    // h(x) = ((a * x + b) mod 2^128) >> (128 - l) for 64 bit x and l bit results, with a odd
     hash_results_num_bits: usize, 
-    rng: ThreadRng,
+    // the functions drawn from the family are fixed by this seed, so a run can be replayed from it
+    seed: u64,
+    rng: StdRng,
 }
 
 impl UniversalHashFunctionsFamily {
     pub fn new(hash_results_num_bits: usize) -> Self {
-        UniversalHashFunctionsFamily { hash_results_num_bits, rng: rand::rng() }
+        Self::with_seed(hash_results_num_bits, rand::rng().random())
+    }
+
+    pub fn with_seed(hash_results_num_bits: usize, seed: u64) -> Self {
+        UniversalHashFunction::assert_valid_num_bits(hash_results_num_bits);
+        UniversalHashFunctionsFamily { hash_results_num_bits, seed, rng: StdRng::seed_from_u64(seed) }
+    }
+
+    pub fn with_rng<R: Rng>(hash_results_num_bits: usize, rng: &mut R) -> Self {
+        Self::with_seed(hash_results_num_bits, rng.random())
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
     }
 
     pub fn construct_new_hash_function(&self, seed_a: u128, seed_b: u128) -> UniversalHashFunction {
@@ -22,6 +39,7 @@ impl UniversalHashFunctionsFamily {
     }
 }
 
+#[derive(Clone, PartialEq)]
 pub struct UniversalHashFunction {
     hash_results_num_bits: usize,
     hash_function_parameter_seed_a: u128, 
@@ -32,7 +50,13 @@ impl UniversalHashFunction {
     const HASH_INPUTS_BIT_NUMS_2X: usize = 64 * 2;
 
     pub fn new(hash_results_num_bits: usize, hash_function_parameter_a: u128, hash_function_parameter_b: u128, ) -> Self {
-        UniversalHashFunction {hash_results_num_bits , hash_function_parameter_seed_a: hash_function_parameter_a, hash_function_parameter_seed_b: hash_function_parameter_b, }
+        Self::assert_valid_num_bits(hash_results_num_bits);
+        // the multiplier has to be odd, an even one throws away its trailing zero bits of every input
+        UniversalHashFunction {hash_results_num_bits , hash_function_parameter_seed_a: hash_function_parameter_a | 1, hash_function_parameter_seed_b: hash_function_parameter_b, }
+    }
+
+    pub(crate) fn assert_valid_num_bits(hash_results_num_bits: usize) {
+        assert!((1..=64).contains(&hash_results_num_bits), "hash results need between 1 and 64 bits, got {}", hash_results_num_bits);
     }
 
     pub fn hash(&self, val: u64) -> u128 {
@@ -41,7 +65,9 @@ impl UniversalHashFunction {
 
     pub fn hash128(&self, val: u64) -> u128 {
         let val_u128 = val as u128;
-        (self.hash_function_parameter_seed_a * val_u128 + self.hash_function_parameter_seed_b) >> (Self::HASH_INPUTS_BIT_NUMS_2X - self.hash_results_num_bits)
+        // mod 2^128 is part of the scheme, so overflow has to wrap in debug builds too
+        // the shift is at least 64 since the results have at most 64 bits
+        self.hash_function_parameter_seed_a.wrapping_mul(val_u128).wrapping_add(self.hash_function_parameter_seed_b) >> (Self::HASH_INPUTS_BIT_NUMS_2X - self.hash_results_num_bits)
     }
 
     pub fn hash64(&self, val: u64) -> u64 {
@@ -51,4 +77,72 @@ impl UniversalHashFunction {
     pub fn hash32(&self, val: u64) -> u32 {
         self.hash64(val) as u32
     }
+
+    pub fn seeds(&self) -> (usize, u128, u128) {
+        (self.hash_results_num_bits, self.hash_function_parameter_seed_a, self.hash_function_parameter_seed_b)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // expected values computed with arbitrary precision integers, so these pass in both
+    // debug and release builds only if both wrap mod 2^128 the same way
+    const KNOWN_ANSWERS: [(u128, u128, u64, usize, u64); 6] = [
+        (u128::MAX, u128::MAX, u64::MAX, 64, 0xffffffffffffffff),
+        (0x9e3779b97f4a7c15f39cc0605cedc835, 0x2545f4914f6cdd1d0123456789abcdef, 0xdeadbeefcafebabe, 64, 0x0819d798aeb26cf8),
+        (0x9e3779b97f4a7c15f39cc0605cedc835, 0x2545f4914f6cdd1d0123456789abcdef, 12345, 32, 0xc672d6bc),
+        (0xf0e1d2c3b4a5968778695a4b3c2d1e0f, 7, 1, 1, 1),
+        (0xf0e1d2c3b4a5968778695a4b3c2d1e0f, 0x0fedcba9876543210fedcba987654321, u64::MAX, 17, 0x12eea),
+        (0, 0, 0, 64, 0),
+    ];
+
+    #[test]
+    fn hash_matches_known_answers_without_overflow_panics() {
+        for (seed_a, seed_b, val, hash_results_num_bits, expected) in KNOWN_ANSWERS {
+            let hash_function = UniversalHashFunctionsFamily::new(hash_results_num_bits).construct_new_hash_function(seed_a, seed_b);
+            assert_eq!(hash_function.hash64(val), expected, "seeds {:#x} {:#x}, val {:#x}, {} bits", seed_a, seed_b, val, hash_results_num_bits);
+        }
+    }
+
+    #[test]
+    fn multiplier_is_odd() {
+        let hash_function = UniversalHashFunction::new(64, 0x10, 0);
+        let (_, seed_a, _) = hash_function.seeds();
+        assert_eq!(seed_a, 0x11);
+    }
+
+    #[test]
+    fn results_fit_in_num_bits() {
+        let mut family = UniversalHashFunctionsFamily::new(13);
+        let hash_function = family.construct_new_hash_function_with_random_seeds();
+        for val in (0..1000u64).chain([u64::MAX - 1, u64::MAX]) {
+            assert!(hash_function.hash64(val) < 1 << 13);
+        }
+    }
+
+    #[test]
+    fn same_seed_draws_same_functions() {
+        use crate::universalhash::hasher::SketchHasher;
+        let mut family_a = UniversalHashFunctionsFamily::with_seed(64, 42);
+        let mut family_b = UniversalHashFunctionsFamily::with_seed(64, 42);
+        let first = family_a.construct_new_hash_function_with_random_seeds();
+        assert!(first == family_b.construct_new_hash_function_with_random_seeds());
+        assert!(family_a.construct_new_hash_function_with_random_seeds() == family_b.construct_new_hash_function_with_random_seeds());
+        assert!(first == UniversalHashFunction::from_seed(42));
+        assert_eq!(family_a.seed(), 42);
+    }
+
+    #[test]
+    #[should_panic]
+    fn zero_bits_are_rejected() {
+        UniversalHashFunctionsFamily::new(0);
+    }
+
+    #[test]
+    #[should_panic]
+    fn more_than_64_bits_are_rejected() {
+        UniversalHashFunction::new(65, 1, 0);
+    }
 }
@@ -0,0 +1,145 @@
+use crate::universalhash::hasher::SketchHasher;
+
+// default secret of wyhash final4
+const WYHASH_SECRET: [u64; 4] = [0x2d358dccaa6c78a5, 0x8bb84b93962eacc9, 0x4b33a62ed433d4a3, 0x4d5a2da51de1aa47];
+
+// wyhash final4 (wang yi) with the default secret and without WYHASH_CONDOM
+pub fn wyhash(bytes: &[u8], seed: u64) -> u64 {
+    let secret = &WYHASH_SECRET;
+    let len = bytes.len();
+    let mut seed = seed ^ wymix(seed ^ secret[0], secret[1]);
+    let (mut a, mut b);
+    if len <= 16 {
+        if len >= 4 {
+            let quarter = (len >> 3) << 2;
+            a = (wyr4(&bytes[0..]) << 32) | wyr4(&bytes[quarter..]);
+            b = (wyr4(&bytes[len - 4..]) << 32) | wyr4(&bytes[len - 4 - quarter..]);
+        }
+        else if len > 0 {
+            a = wyr3(bytes);
+            b = 0;
+        }
+        else {
+            a = 0;
+            b = 0;
+        }
+    }
+    else {
+        let mut offset = 0;
+        let mut remaining = len;
+        if remaining >= 48 {
+            let (mut see1, mut see2) = (seed, seed);
+            while remaining >= 48 {
+                seed = wymix(wyr8(&bytes[offset..]) ^ secret[1], wyr8(&bytes[offset + 8..]) ^ seed);
+                see1 = wymix(wyr8(&bytes[offset + 16..]) ^ secret[2], wyr8(&bytes[offset + 24..]) ^ see1);
+                see2 = wymix(wyr8(&bytes[offset + 32..]) ^ secret[3], wyr8(&bytes[offset + 40..]) ^ see2);
+                offset += 48;
+                remaining -= 48;
+            }
+            seed ^= see1 ^ see2;
+        }
+        while remaining > 16 {
+            seed = wymix(wyr8(&bytes[offset..]) ^ secret[1], wyr8(&bytes[offset + 8..]) ^ seed);
+            offset += 16;
+            remaining -= 16;
+        }
+        // the last 16 bytes, which can overlap bytes that were already mixed in
+        a = wyr8(&bytes[offset + remaining - 16..]);
+        b = wyr8(&bytes[offset + remaining - 8..]);
+    }
+    a ^= secret[1];
+    b ^= seed;
+    (a, b) = wymum(a, b);
+    wymix(a ^ secret[0] ^ len as u64, b ^ secret[1])
+}
+
+fn wymum(a: u64, b: u64) -> (u64, u64) {
+    let product = a as u128 * b as u128;
+    (product as u64, (product >> 64) as u64)
+}
+
+fn wymix(a: u64, b: u64) -> u64 {
+    let (low, high) = wymum(a, b);
+    low ^ high
+}
+
+fn wyr8(bytes: &[u8]) -> u64 {
+    u64::from_le_bytes(bytes[..8].try_into().unwrap())
+}
+
+fn wyr4(bytes: &[u8]) -> u64 {
+    u32::from_le_bytes(bytes[..4].try_into().unwrap()) as u64
+}
+
+fn wyr3(bytes: &[u8]) -> u64 {
+    let len = bytes.len();
+    ((bytes[0] as u64) << 16) | ((bytes[len >> 1] as u64) << 8) | bytes[len - 1] as u64
+}
+
+#[derive(Clone, PartialEq)]
+pub struct WyHash {
+    seed: u64,
+}
+
+impl WyHash {
+    pub fn new(seed: u64) -> Self {
+        WyHash { seed }
+    }
+
+    pub fn hash_bytes(&self, bytes: &[u8]) -> u64 {
+        wyhash(bytes, self.seed)
+    }
+
+    // items are hashed as their 8 little endian bytes
+    pub fn hash64(&self, val: u64) -> u64 {
+        self.hash_bytes(&val.to_le_bytes())
+    }
+}
+
+impl SketchHasher for WyHash {
+    fn hash64(&self, val: u64) -> u64 {
+        WyHash::hash64(self, val)
+    }
+
+    fn from_seed(seed: u64) -> Self {
+        Self::new(seed)
+    }
+
+    fn parameters(&self) -> Vec<u8> {
+        self.seed.to_le_bytes().to_vec()
+    }
+
+    fn from_parameters(bytes: &[u8]) -> Option<Self> {
+        Some(Self::new(u64::from_le_bytes(bytes.try_into().ok()?)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // test vectors of the reference implementation, the seed is the index
+    const KNOWN_ANSWERS: [(&[u8], u64); 7] = [
+        (b"", 0x93228a4de0eec5a2),
+        (b"a", 0xc5bac3db178713c4),
+        (b"abc", 0xa97f2f7b1d9b3314),
+        (b"message digest", 0x786d1f1df3801df4),
+        (b"abcdefghijklmnopqrstuvwxyz", 0xdca5a8138ad37c87),
+        (b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789", 0xb9e734f117cfaf70),
+        (b"12345678901234567890123456789012345678901234567890123456789012345678901234567890", 0x6cc5eab49a92d617),
+    ];
+
+    #[test]
+    fn wyhash_known_answers() {
+        for (seed, (bytes, expected)) in KNOWN_ANSWERS.iter().enumerate() {
+            assert_eq!(wyhash(bytes, seed as u64), *expected, "{:?}", std::str::from_utf8(bytes));
+        }
+    }
+
+    #[test]
+    fn hasher_uses_little_endian_bytes() {
+        let hasher = WyHash::new(7);
+        assert_eq!(hasher.hash64(42), wyhash(&42u64.to_le_bytes(), 7));
+        assert!(WyHash::from_parameters(&hasher.parameters()) == Some(hasher));
+    }
+}
@@ -0,0 +1,120 @@
+use crate::universalhash::hasher::SketchHasher;
+
+const PRIME64_1: u64 = 0x9E3779B185EBCA87;
+const PRIME64_2: u64 = 0xC2B2AE3D27D4EB4F;
+const PRIME64_3: u64 = 0x165667B19E3779F9;
+const PRIME64_4: u64 = 0x85EBCA77C2B2AE63;
+const PRIME64_5: u64 = 0x27D4EB2F165667C5;
+
+// xxh64 (yann collet)
+pub fn xxhash64(bytes: &[u8], seed: u64) -> u64 {
+    let mut stripes = bytes.chunks_exact(32);
+    let mut hashed = if bytes.len() >= 32 {
+        let mut accumulators = [
+            seed.wrapping_add(PRIME64_1).wrapping_add(PRIME64_2),
+            seed.wrapping_add(PRIME64_2),
+            seed,
+            seed.wrapping_sub(PRIME64_1),
+        ];
+        for stripe in &mut stripes {
+            for (accumulator, lane) in accumulators.iter_mut().zip(stripe.chunks_exact(8)) {
+                *accumulator = round(*accumulator, u64::from_le_bytes(lane.try_into().unwrap()));
+            }
+        }
+        let mut hashed = accumulators[0].rotate_left(1)
+            .wrapping_add(accumulators[1].rotate_left(7))
+            .wrapping_add(accumulators[2].rotate_left(12))
+            .wrapping_add(accumulators[3].rotate_left(18));
+        for accumulator in accumulators {
+            hashed = (hashed ^ round(0, accumulator)).wrapping_mul(PRIME64_1).wrapping_add(PRIME64_4);
+        }
+        hashed
+    }
+    else {
+        seed.wrapping_add(PRIME64_5)
+    };
+    hashed = hashed.wrapping_add(bytes.len() as u64);
+    let mut remainder = stripes.remainder();
+    while remainder.len() >= 8 {
+        hashed ^= round(0, u64::from_le_bytes(remainder[..8].try_into().unwrap()));
+        hashed = hashed.rotate_left(27).wrapping_mul(PRIME64_1).wrapping_add(PRIME64_4);
+        remainder = &remainder[8..];
+    }
+    if remainder.len() >= 4 {
+        hashed ^= (u32::from_le_bytes(remainder[..4].try_into().unwrap()) as u64).wrapping_mul(PRIME64_1);
+        hashed = hashed.rotate_left(23).wrapping_mul(PRIME64_2).wrapping_add(PRIME64_3);
+        remainder = &remainder[4..];
+    }
+    for &byte in remainder {
+        hashed ^= (byte as u64).wrapping_mul(PRIME64_5);
+        hashed = hashed.rotate_left(11).wrapping_mul(PRIME64_1);
+    }
+    hashed ^= hashed >> 33;
+    hashed = hashed.wrapping_mul(PRIME64_2);
+    hashed ^= hashed >> 29;
+    hashed = hashed.wrapping_mul(PRIME64_3);
+    hashed ^ (hashed >> 32)
+}
+
+fn round(accumulator: u64, lane: u64) -> u64 {
+    accumulator.wrapping_add(lane.wrapping_mul(PRIME64_2)).rotate_left(31).wrapping_mul(PRIME64_1)
+}
+
+#[derive(Clone, PartialEq)]
+pub struct XxHash64 {
+    seed: u64,
+}
+
+impl XxHash64 {
+    pub fn new(seed: u64) -> Self {
+        XxHash64 { seed }
+    }
+
+    pub fn hash_bytes(&self, bytes: &[u8]) -> u64 {
+        xxhash64(bytes, self.seed)
+    }
+
+    // items are hashed as their 8 little endian bytes
+    pub fn hash64(&self, val: u64) -> u64 {
+        self.hash_bytes(&val.to_le_bytes())
+    }
+}
+
+impl SketchHasher for XxHash64 {
+    fn hash64(&self, val: u64) -> u64 {
+        XxHash64::hash64(self, val)
+    }
+
+    fn from_seed(seed: u64) -> Self {
+        Self::new(seed)
+    }
+
+    fn parameters(&self) -> Vec<u8> {
+        self.seed.to_le_bytes().to_vec()
+    }
+
+    fn from_parameters(bytes: &[u8]) -> Option<Self> {
+        Some(Self::new(u64::from_le_bytes(bytes.try_into().ok()?)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn xxhash64_known_answers() {
+        assert_eq!(xxhash64(b"", 0), 0xef46db3751d8e999);
+        assert_eq!(xxhash64(b"a", 0), 0xd24ec4f1a98c6e5b);
+        assert_eq!(xxhash64(b"abc", 0), 0x44bc2cf5ad770999);
+        // long enough for the 32 byte stripes
+        assert_eq!(xxhash64(b"Nobody inspects the spammish repetition", 0), 0xfbcea83c8a378bf1);
+    }
+
+    #[test]
+    fn hasher_uses_little_endian_bytes() {
+        let hasher = XxHash64::new(7);
+        assert_eq!(hasher.hash64(42), xxhash64(&42u64.to_le_bytes(), 7));
+        assert!(XxHash64::from_parameters(&hasher.parameters()) == Some(hasher));
+    }
+}
@@ -1,49 +1,65 @@
 use std::hash::Hash;
+use rand::prelude::*;
 use crate::universalhash::*;
+use crate::universalhash::hasher::SketchHasher;
+use crate::linearcounting::linearcounting::LinearCounting;
 
 type HashResult = u64;
-pub struct HLL {
+pub struct HLL<H: SketchHasher = univesalhash::UniversalHashFunction> {
     num_bucket_bits: usize,
     num_buckets: usize,
     bias_correction_value: f64,
+    // ranks (position of the first one bit after the bucket bits), 0 for empty registers
     buckets: Vec<usize>, 
-    hash_function: univesalhash::UniversalHashFunction,
+    hash_function: H,
+    seed: Option<u64>,
 }
 
 impl HLL {
     pub fn new(num_bucket_bits: usize) -> Self {
+        Self::with_seed(num_bucket_bits, rand::rng().random())
+    }
+
+    pub fn with_seed(num_bucket_bits: usize, seed: u64) -> Self {
+        let mut hll = Self::with_hash_function(num_bucket_bits, univesalhash::UniversalHashFunction::from_seed(seed));
+        hll.seed = Some(seed);
+        hll
+    }
+}
+
+impl<H: SketchHasher> HLL<H> {
+    pub fn with_hash_function(num_bucket_bits: usize, hash_function: H) -> Self {
         let num_buckets = 1 << num_bucket_bits;
         // dbg!(num_bucket_bits);
         HLL { 
             num_bucket_bits: num_bucket_bits,
             num_buckets: num_buckets,
-            bias_correction_value: Self::compute_bias_correction_value(num_buckets),
+            bias_correction_value: HLL::compute_bias_correction_value(num_buckets),
             buckets: vec![0;num_buckets],
-            hash_function: univesalhash::UniversalHashFunctionsFamily::new(64).construct_new_hash_function_with_random_seeds(),
+            hash_function,
+            seed: None,
         }
     }
 
+    // none when the hash function was handed in directly
+    pub fn seed(&self) -> Option<u64> {
+        self.seed
+    }
+
     pub fn read_stream(&mut self, input_stream: &mut Box<dyn Iterator<Item = u64>>){
         input_stream.map(|data| self.hash_function.hash64(data))
-            .map(|hashed_data| (Self::get_bucket_idx(self.num_bucket_bits, hashed_data), Self::get_data_bits(self.num_bucket_bits, hashed_data)))
+            .map(|hashed_data| (HLL::get_bucket_idx(self.num_bucket_bits, hashed_data), HLL::get_data_bits(self.num_bucket_bits, hashed_data)))
             .for_each(|(bucket_idx, data_bits)| {
-                let leading_zeros = data_bits.leading_zeros() as usize;
-                if leading_zeros < self.buckets.get(bucket_idx).unwrap_or(&0).clone() {
-                    self.buckets[bucket_idx] = leading_zeros;
-                }
+                HLL::update_bucket(&mut self.buckets, bucket_idx, HLL::get_rank(self.num_bucket_bits, data_bits));
             })
         ;
     }
     pub fn read_data(&mut self, data: u64){
         let hashed_data = self.hash(data);
         // dbg!((data, hashed_data));
-        let (bucket_idx, data_bits) = (Self::get_bucket_idx(self.num_bucket_bits, hashed_data), Self::get_data_bits(self.num_bucket_bits, hashed_data));
-        let leading_zeros = data_bits.leading_zeros() as usize;
+        let (bucket_idx, data_bits) = (HLL::get_bucket_idx(self.num_bucket_bits, hashed_data), HLL::get_data_bits(self.num_bucket_bits, hashed_data));
         // dbg!((bucket_idx, data_bits));
-        // dbg!(leading_zeros);
-        if leading_zeros > self.buckets.get(bucket_idx).unwrap_or(&0).clone() {
-            self.buckets[bucket_idx] = leading_zeros;
-        }
+        HLL::update_bucket(&mut self.buckets, bucket_idx, HLL::get_rank(self.num_bucket_bits, data_bits));
     }
     
     pub fn get_cardinality(&mut self) -> f64 {
@@ -54,30 +70,51 @@ impl HLL {
     pub fn buckets(&self) -> &Vec<usize> {
         &self.buckets
     }
+
+    fn hash(&self, val: u64) -> HashResult {
+        self.hash_function.hash64(val)
+    }
+
+    fn compute_estimates(&mut self) -> f64 {
+        let raw_estimates = self.bias_correction_value * HLL::compute_mean_max_leading_zeroes(&self.buckets);
+        // println!("raw_estimates: {}",raw_estimates);
+        HLL::perform_correction(raw_estimates, &self.buckets)
+    }
+}
+
+// helpers that do not depend on the hash function
+impl HLL {
+    // register-max update, shared with sketches that keep their registers somewhere else
+    pub(crate) fn update_bucket(buckets: &mut [usize], bucket_idx: usize, rank: usize) {
+        if rank > buckets.get(bucket_idx).unwrap_or(&0).clone() {
+            buckets[bucket_idx] = rank;
+        }
+    }
+
+    // the leading zeros of the data bits include the zeroed bucket bits, the rank starts counting after them
+    pub(crate) fn get_rank(num_bucket_bits: usize, data_bits: HashResult) -> usize {
+        data_bits.leading_zeros() as usize - num_bucket_bits + 1
+    }
     
     fn compute_bias_correction_value(num_buckets: usize) -> f64 {
-        match num_buckets {
+        (match num_buckets {
             // numbers precomputed from the paper
             16 => 0.673,
             32 => 0.697,
             64 => 0.709,
             // formula from hll paper
-            _ => (0.7213 / (1f64 + (1.079 / (num_buckets as f64)))) * (num_buckets.pow(2) as f64)
-        }
-    }
-
-    fn hash(&self, val: u64) -> HashResult {
-        self.hash_function.hash64(val)
+            _ => 0.7213 / (1f64 + (1.079 / (num_buckets as f64)))
+        }) * (num_buckets.pow(2) as f64)
     }
 
-    fn compute_estimates(&mut self) -> f64 {
-        let raw_estimates = self.bias_correction_value * Self::compute_mean_max_leading_zeroes(&self.buckets);
-        // println!("raw_estimates: {}",raw_estimates);
-        Self::perform_correction(raw_estimates, &self.buckets)
+    // same estimates as get_cardinality for sketches that keep the same ranks in another layout
+    pub fn compute_estimates_from_buckets(buckets: &Vec<usize>) -> f64 {
+        let raw_estimates = Self::compute_bias_correction_value(buckets.len()) * Self::compute_mean_max_leading_zeroes(buckets);
+        Self::perform_correction(raw_estimates, buckets)
     }
 
     fn compute_mean_max_leading_zeroes(buckets: &Vec<usize>) -> f64 {
-        1.0f64  / (buckets.iter().map(|num_leading_zeroes| 1f64 / ((1 << num_leading_zeroes) as f64)).fold(0f64, |left,right| left + right))
+        1.0f64  / (buckets.iter().map(|&rank| 2f64.powi(-(rank as i32))).fold(0f64, |left,right| left + right))
     }
 
     fn perform_correction(raw_estimates: f64, buckets: &Vec<usize>) -> f64 {
@@ -103,21 +140,13 @@ impl HLL {
         let num_empty_buckets = Self::get_num_empty_buckets(buckets);
         if num_empty_buckets != 0 {
             // println!("Linear Countings!");
-            Self::get_linear_counting_cardinality_estimates(buckets.len(), num_empty_buckets)
+            LinearCounting::linear_counting(buckets.len(), num_empty_buckets)
         }
         else {
             raw_estimates
         }
     }
 
-    fn get_linear_counting_cardinality_estimates(num_buckets: usize, num_empty_buckets: usize) -> f64 {
-        let num_buckets_f64 = num_buckets as f64;
-        let num_empty_buckets_f64 = num_empty_buckets as f64;
-        // dbg!(num_buckets_f64);
-        // dbg!(num_empty_buckets_f64);
-        num_buckets_f64 * f64::ln(num_buckets_f64 / num_empty_buckets_f64)
-    }
-    
 
     fn get_num_empty_buckets(buckets: &Vec<usize>) -> usize {
         // dbg!(buckets.iter().all(|&a| a==0));
@@ -136,13 +165,13 @@ impl HLL {
         - 2f64.powf(32.0) * f64::ln( 1f64 - (raw_estimates / (2f64.powf(32.0))))
     }
 
-    fn get_bucket_idx(num_bucket_bits: usize, data: HashResult) -> usize {
+    pub(crate) fn get_bucket_idx(num_bucket_bits: usize, data: HashResult) -> usize {
         // println!("data = {:#016x}",data);
         // dbg!((data >> (64 - num_bucket_bits)) as usize)
         (data >> (64 - num_bucket_bits)) as usize
     }
 
-    fn get_data_bits(num_bucket_bits: usize, data: HashResult) -> HashResult {
+    pub(crate) fn get_data_bits(num_bucket_bits: usize, data: HashResult) -> HashResult {
         // get only the non buckets idx bits
         // idea: shift left by nums of bucket bits
         // then shift right back, now the buckets bits are all 0s
@@ -153,3 +182,50 @@ impl HLL {
 
 
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::*;
+
+    fn estimate(num_bucket_bits: usize, num_items: usize) -> f64 {
+        let mut hll = HLL::with_seed(num_bucket_bits, 1);
+        for data in random_items(2, num_items) {
+            hll.read_data(data);
+        }
+        hll.get_cardinality()
+    }
+
+    #[test]
+    fn estimates_are_accurate_over_every_range() {
+        // standard error is 1.04 / sqrt(2^12) = 1.6%, 1000 is linear counting, 30k and 1m are the raw estimate
+        for num_items in [1000, 30_000, 1_000_000] {
+            let estimates = estimate(12, num_items);
+            assert!((estimates - num_items as f64).abs() < 0.06 * num_items as f64, "{} items, estimate {}", num_items, estimates);
+        }
+    }
+
+    #[test]
+    fn tabulated_bias_correction_is_scaled() {
+        // 16 registers take the precomputed alpha, the standard error is 26% so only check the order of magnitude
+        let estimates = estimate(4, 10_000);
+        assert!(estimates > 5000f64 && estimates < 20_000f64, "estimate {}", estimates);
+    }
+
+    #[test]
+    fn registers_hold_ranks() {
+        // data bits 0001... after the 4 zeroed bucket bits are a rank of 4
+        assert_eq!(HLL::get_rank(4, 0x01ff_ffff_ffff_ffff), 4);
+        assert_eq!(HLL::get_rank(4, 0x0800_0000_0000_0000), 1);
+    }
+
+    #[test]
+    fn read_stream_equals_read_data() {
+        let items = random_items(3, 10_000);
+        let (mut streamed, mut single) = (HLL::with_seed(10, 4), HLL::with_seed(10, 4));
+        streamed.read_stream(&mut (Box::new(items.clone().into_iter()) as Box<dyn Iterator<Item = u64>>));
+        for data in items {
+            single.read_data(data);
+        }
+        assert_eq!(streamed.buckets(), single.buckets());
+    }
+}
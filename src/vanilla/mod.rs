@@ -1 +1,3 @@
-pub mod hll;
\ No newline at end of file
+pub mod hll;
+pub mod slidinghll;
+pub mod vhll;
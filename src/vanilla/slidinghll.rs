@@ -0,0 +1,155 @@
+use std::collections::VecDeque;
+use rand::prelude::*;
+use crate::universalhash::*;
+use crate::vanilla::hll::HLL;
+
+// sliding window hll (chabchoub & hebrail 2010)
+// instead of a single max every register keeps its list of future possible maxima (lfpm):
+// the (timestamp, rank) pairs that are still the max of some window ending at the latest timestamp,
+// so timestamps increase and ranks strictly decrease along the list
+// any window up to max_window is answered by taking, per register, the first pair that is still inside it
+pub struct SlidingHLL {
+    num_bucket_bits: usize,
+    max_window: u64,
+    latest_timestamp: u64,
+    lfpms: Vec<VecDeque<(u64, u8)>>,
+    hash_function: univesalhash::UniversalHashFunction,
+    seed: Option<u64>,
+}
+
+impl SlidingHLL {
+    pub fn new(num_bucket_bits: usize, max_window: u64) -> Self {
+        Self::with_seed(num_bucket_bits, max_window, rand::rng().random())
+    }
+
+    pub fn with_seed(num_bucket_bits: usize, max_window: u64, seed: u64) -> Self {
+        let mut sliding_hll = Self::with_hash_function(num_bucket_bits, max_window, univesalhash::UniversalHashFunctionsFamily::with_seed(64, seed).construct_new_hash_function_with_random_seeds());
+        sliding_hll.seed = Some(seed);
+        sliding_hll
+    }
+
+    pub fn with_hash_function(num_bucket_bits: usize, max_window: u64, hash_function: univesalhash::UniversalHashFunction) -> Self {
+        assert!(max_window > 0, "sliding hll needs a max window > 0");
+        Self {
+            num_bucket_bits,
+            max_window,
+            latest_timestamp: 0,
+            lfpms: vec![VecDeque::new(); 1 << num_bucket_bits],
+            hash_function,
+            seed: None,
+        }
+    }
+
+    pub fn seed(&self) -> Option<u64> {
+        self.seed
+    }
+
+    // timestamps are in whatever unit the windows are in and must not go backwards
+    pub fn read_data(&mut self, data: u64, timestamp: u64) {
+        assert!(timestamp >= self.latest_timestamp, "sliding hll needs non decreasing timestamps");
+        self.latest_timestamp = timestamp;
+        let hashed_data = self.hash_function.hash64(data);
+        let (bucket_idx, data_bits) = (HLL::get_bucket_idx(self.num_bucket_bits, hashed_data), HLL::get_data_bits(self.num_bucket_bits, hashed_data));
+        // position of the first one bit after the bucket bits
+        let rank = HLL::get_rank(self.num_bucket_bits, data_bits) as u8;
+        let lfpm = &mut self.lfpms[bucket_idx];
+        // older pairs that are not bigger can never be the max of a window again
+        while lfpm.back().is_some_and(|&(_, other_rank)| other_rank <= rank) {
+            lfpm.pop_back();
+        }
+        lfpm.push_back((timestamp, rank));
+        while lfpm.front().is_some_and(|&(other_timestamp, _)| !Self::is_in_window(other_timestamp, timestamp, self.max_window)) {
+            lfpm.pop_front();
+        }
+    }
+
+    // distinct items among the ones read with a timestamp in the last window units before (and at) the latest timestamp
+    pub fn compute_estimates(&self, window: u64) -> f64 {
+        assert!(window <= self.max_window, "sliding hll can only answer windows up to its max window");
+        let buckets: Vec<usize> = self.lfpms.iter()
+            .map(|lfpm| lfpm.iter()
+                .find(|&&(timestamp, _)| Self::is_in_window(timestamp, self.latest_timestamp, window))
+                .map_or(0, |&(_, rank)| rank as usize)
+            )
+            .collect();
+        HLL::compute_estimates_from_buckets(&buckets)
+    }
+
+    pub fn num_stored_pairs(&self) -> usize {
+        self.lfpms.iter().map(|lfpm| lfpm.len()).sum()
+    }
+
+    // timestamp <= latest_timestamp always holds, so this neither underflows nor overflows for timestamps or windows near u64::MAX
+    fn is_in_window(timestamp: u64, latest_timestamp: u64, window: u64) -> bool {
+        latest_timestamp - timestamp < window
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::*;
+
+    const NUM_TIMESTAMPS: u64 = 100;
+    const NUM_ITEMS_PER_TIMESTAMP: usize = 500;
+
+    // NUM_ITEMS_PER_TIMESTAMP new items at every timestamp in 0..NUM_TIMESTAMPS
+    fn timestamped_items() -> Vec<(u64, u64)> {
+        let items = random_items(1, NUM_TIMESTAMPS as usize * NUM_ITEMS_PER_TIMESTAMP);
+        items.into_iter().enumerate().map(|(i, data)| (data, (i / NUM_ITEMS_PER_TIMESTAMP) as u64)).collect()
+    }
+
+    fn sliding_hll_of(max_window: u64, timestamped_items: impl Iterator<Item = (u64, u64)>) -> SlidingHLL {
+        let mut sliding_hll = SlidingHLL::with_hash_function(10, max_window, hash_function(2));
+        for (data, timestamp) in timestamped_items {
+            sliding_hll.read_data(data, timestamp);
+        }
+        sliding_hll
+    }
+
+    // a sketch that only ever read the items of the window, so its registers are the plain max ranks of them
+    fn estimates_of_window_items(window: u64) -> f64 {
+        let window_items = timestamped_items().into_iter().filter(|&(_, timestamp)| timestamp + window >= NUM_TIMESTAMPS);
+        sliding_hll_of(NUM_TIMESTAMPS, window_items).compute_estimates(NUM_TIMESTAMPS)
+    }
+
+    #[test]
+    fn windows_equal_a_sketch_of_their_items() {
+        // the window of w ends at the latest timestamp 99, so it covers 100 - w..=99 and not 99 - w
+        let sliding_hll = sliding_hll_of(50, timestamped_items().into_iter());
+        for window in [1, 2, 10, 49, 50] {
+            assert_eq!(sliding_hll.compute_estimates(window), estimates_of_window_items(window), "window {}", window);
+        }
+        // standard error is 1.04 / sqrt(2^10) = 3.3%
+        let estimates = sliding_hll.compute_estimates(10);
+        assert!((estimates - 5000f64).abs() < 0.12 * 5000f64, "estimate {}", estimates);
+    }
+
+    #[test]
+    fn empty_window_estimates_0() {
+        assert_eq!(sliding_hll_of(50, timestamped_items().into_iter()).compute_estimates(0), 0f64);
+    }
+
+    #[test]
+    fn pairs_older_than_max_window_are_dropped() {
+        // the lfpms only keep decreasing ranks inside the max window, a few per register instead of every item
+        let sliding_hll = sliding_hll_of(10, timestamped_items().into_iter());
+        assert_eq!(sliding_hll.compute_estimates(10), estimates_of_window_items(10));
+        assert!(sliding_hll.num_stored_pairs() < 10 * (1 << 10), "{} pairs", sliding_hll.num_stored_pairs());
+    }
+
+    #[test]
+    fn timestamps_and_windows_near_u64_max() {
+        // every item stays in the unbounded max window, a window of 1 only covers the latest timestamp
+        let late_items = timestamped_items().into_iter().map(|(data, timestamp)| (data, u64::MAX - NUM_TIMESTAMPS + 1 + timestamp));
+        let sliding_hll = sliding_hll_of(u64::MAX, late_items);
+        assert_eq!(sliding_hll.compute_estimates(u64::MAX), sliding_hll.compute_estimates(NUM_TIMESTAMPS));
+        assert_eq!(sliding_hll.compute_estimates(1), estimates_of_window_items(1));
+    }
+
+    #[test]
+    #[should_panic]
+    fn windows_over_max_window_panic() {
+        sliding_hll_of(10, timestamped_items().into_iter()).compute_estimates(11);
+    }
+}
@@ -0,0 +1,169 @@
+use rand::prelude::*;
+use rand::rngs::StdRng;
+use crate::universalhash::*;
+use crate::universalhash::hasher::SketchHasher;
+use crate::vanilla::hll::HLL;
+
+// virtual hll (xiao et al. 2015)
+// every flow gets 2^num_virtual_bucket_bits virtual registers, picked pseudo-randomly from one shared pool of physical registers
+// an item of a flow updates one of the flow's virtual registers like hll would, so the flow's registers also
+// see noise from every other flow sharing them; that noise is estimated from the whole pool and subtracted
+pub struct VHLL {
+    num_virtual_bucket_bits: usize,
+    // ranks (position of the first one bit after the bucket bits), 0 for empty registers
+    physical_buckets: Vec<usize>,
+    // one seed per virtual register, mixed with the flow to pick the physical register
+    virtual_bucket_seeds: Vec<u64>,
+    hash_function: univesalhash::UniversalHashFunction,
+    seed: Option<u64>,
+}
+
+impl VHLL {
+    pub fn new(num_physical_buckets: usize, num_virtual_bucket_bits: usize) -> Self {
+        Self::with_seed(num_physical_buckets, num_virtual_bucket_bits, rand::rng().random())
+    }
+
+    // the hash function and the virtual register seeds come from one stream so the seed fixes both
+    pub fn with_seed(num_physical_buckets: usize, num_virtual_bucket_bits: usize, seed: u64) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let hash_function = univesalhash::UniversalHashFunctionsFamily::with_rng(64, &mut rng).construct_new_hash_function_with_random_seeds();
+        let mut vhll = Self::with_rng(num_physical_buckets, num_virtual_bucket_bits, hash_function, &mut rng);
+        vhll.seed = Some(seed);
+        vhll
+    }
+
+    // the virtual register seeds are derived from the hash function's parameters,
+    // so vhlls built from the same hash function map every flow to the same physical registers
+    pub fn with_hash_function(num_physical_buckets: usize, num_virtual_bucket_bits: usize, hash_function: univesalhash::UniversalHashFunction) -> Self {
+        let mut rng_seed = [0u8; 32];
+        for (idx, byte) in hash_function.parameters().into_iter().enumerate() {
+            rng_seed[idx % rng_seed.len()] ^= byte;
+        }
+        Self::with_rng(num_physical_buckets, num_virtual_bucket_bits, hash_function, &mut StdRng::from_seed(rng_seed))
+    }
+
+    fn with_rng<R: Rng>(num_physical_buckets: usize, num_virtual_bucket_bits: usize, hash_function: univesalhash::UniversalHashFunction, rng: &mut R) -> Self {
+        let num_virtual_buckets = 1 << num_virtual_bucket_bits;
+        assert!(num_physical_buckets > num_virtual_buckets, "vhll needs more physical registers than virtual registers per flow");
+        Self {
+            num_virtual_bucket_bits,
+            physical_buckets: vec![0; num_physical_buckets],
+            virtual_bucket_seeds: (0..num_virtual_buckets).map(|_| rng.random()).collect(),
+            hash_function,
+            seed: None,
+        }
+    }
+
+    pub fn seed(&self) -> Option<u64> {
+        self.seed
+    }
+
+    pub fn read_data(&mut self, flow: u64, data: u64) {
+        let hashed_data = self.hash_function.hash64(data);
+        let (virtual_bucket_idx, data_bits) = (HLL::get_bucket_idx(self.num_virtual_bucket_bits, hashed_data), HLL::get_data_bits(self.num_virtual_bucket_bits, hashed_data));
+        let rank = HLL::get_rank(self.num_virtual_bucket_bits, data_bits);
+        let physical_bucket_idx = self.get_physical_bucket_idx(flow, virtual_bucket_idx);
+        HLL::update_bucket(&mut self.physical_buckets, physical_bucket_idx, rank);
+    }
+
+    // n_f = m s / (m - s) * (n_s / s - n_m / m), with n_s the hll estimate of the flow's virtual registers
+    // and n_m the hll estimate of the whole pool
+    pub fn compute_estimates(&self, flow: u64) -> f64 {
+        self.compute_estimates_with_total(flow, self.compute_total_estimates())
+    }
+
+    // same as compute_estimates for many flows, but only goes over the pool once
+    pub fn compute_all_estimates(&self, flows: &[u64]) -> Vec<f64> {
+        let total_estimates = self.compute_total_estimates();
+        flows.iter().map(|&flow| self.compute_estimates_with_total(flow, total_estimates)).collect()
+    }
+
+    // noise level of the pool, roughly the number of distinct (flow, item) pairs as long as every flow has few items
+    // next to its number of virtual registers (the items of a large flow share its registers and are mostly not counted)
+    pub fn compute_total_estimates(&self) -> f64 {
+        HLL::compute_estimates_from_buckets(&self.physical_buckets)
+    }
+
+    fn compute_estimates_with_total(&self, flow: u64, total_estimates: f64) -> f64 {
+        let virtual_buckets: Vec<usize> = (0..self.virtual_bucket_seeds.len())
+            .map(|virtual_bucket_idx| self.physical_buckets[self.get_physical_bucket_idx(flow, virtual_bucket_idx)])
+            .collect();
+        let (num_physical_buckets_f64, num_virtual_buckets_f64) = (self.physical_buckets.len() as f64, virtual_buckets.len() as f64);
+        let flow_estimates = HLL::compute_estimates_from_buckets(&virtual_buckets);
+        num_physical_buckets_f64 * num_virtual_buckets_f64 / (num_physical_buckets_f64 - num_virtual_buckets_f64)
+            * (flow_estimates / num_virtual_buckets_f64 - total_estimates / num_physical_buckets_f64)
+    }
+
+    fn get_physical_bucket_idx(&self, flow: u64, virtual_bucket_idx: usize) -> usize {
+        let hashed_flow = self.hash_function.hash64(flow ^ self.virtual_bucket_seeds[virtual_bucket_idx]);
+        // multiply-shift range reduction onto the pool
+        ((hashed_flow as u128 * self.physical_buckets.len() as u128) >> 64) as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::*;
+    use rand::rngs::StdRng;
+
+    // flow_sizes[i] = (number of flows, items per flow), flows get random ids (returned in that order) and are read in random order
+    // the pool has 2^15 registers, 256 of them per flow
+    fn vhll(flow_sizes: &[(u64, usize)]) -> (VHLL, Vec<u64>) {
+        let mut vhll = VHLL::with_seed(1 << 15, 8, 1);
+        let mut rng = StdRng::seed_from_u64(2);
+        let flows = random_items(3, flow_sizes.iter().map(|&(num_flows, _)| num_flows as usize).sum());
+        let mut flow_items: Vec<(u64, u64)> = Vec::new();
+        let mut first_flow = 0;
+        for &(num_flows, num_items) in flow_sizes {
+            for &flow in &flows[first_flow..first_flow + num_flows as usize] {
+                flow_items.extend((0..num_items).map(|_| (flow, rng.random::<u64>())));
+            }
+            first_flow += num_flows as usize;
+        }
+        flow_items.shuffle(&mut rng);
+        for (flow, data) in flow_items {
+            vhll.read_data(flow, data);
+        }
+        (vhll, flows)
+    }
+
+    #[test]
+    fn total_estimates_count_every_pair_of_small_flows() {
+        // with 5 items per flow hardly any 2 items of a flow share a register, so the pool is a plain hll of 100k pairs
+        // standard error is 1.04 / sqrt(2^15) = 0.6%
+        let total_estimates = vhll(&[(20_000, 5)]).0.compute_total_estimates();
+        assert!((total_estimates - 100_000f64).abs() < 0.03 * 100_000f64, "total estimate {}", total_estimates);
+        // a large flow piles its items onto its own 256 registers, so the pool only sees a fraction of them,
+        // which is fine since the total is only there to estimate the noise every flow's registers get
+        let total_estimates = vhll(&[(20_000, 5), (5, 20_000)]).0.compute_total_estimates();
+        assert!(total_estimates < 150_000f64, "total estimate {}", total_estimates);
+    }
+
+    #[test]
+    fn flow_estimates_subtract_the_noise() {
+        // 1000 flows of 100 items and 5 flows of 20k items, so the noise is about 6 items per register
+        let (vhll, flows) = vhll(&[(1000, 100), (5, 20_000)]);
+        let estimates = vhll.compute_all_estimates(&flows);
+        assert_eq!(estimates[0], vhll.compute_estimates(flows[0]));
+        // the large flows are mostly signal, standard error 1.04 / sqrt(2^8) = 6.5%
+        for (flow, &flow_estimates) in estimates.iter().enumerate().skip(1000) {
+            assert!((flow_estimates - 20_000f64).abs() < 0.25 * 20_000f64, "flow {}: estimate {}", flow, flow_estimates);
+        }
+        // a single small flow is mostly noise (about 1500 items of it), but the noise is subtracted so their mean is close
+        let mean_small_flow_estimates = estimates[..1000].iter().sum::<f64>() / 1000f64;
+        assert!((mean_small_flow_estimates - 100f64).abs() < 20f64, "mean small flow estimate {}", mean_small_flow_estimates);
+    }
+
+    #[test]
+    fn same_hash_function_gives_same_registers() {
+        let (mut vhll, mut other) = (VHLL::with_hash_function(1 << 15, 8, hash_function(4)), VHLL::with_hash_function(1 << 15, 8, hash_function(4)));
+        assert_eq!(vhll.virtual_bucket_seeds, other.virtual_bucket_seeds);
+        for (flow, data) in random_items(5, 1000).into_iter().zip(random_items(6, 1000)) {
+            vhll.read_data(flow % 10, data);
+            other.read_data(flow % 10, data);
+        }
+        assert_eq!(vhll.physical_buckets, other.physical_buckets);
+        assert_ne!(VHLL::with_hash_function(1 << 15, 8, hash_function(7)).virtual_bucket_seeds, vhll.virtual_bucket_seeds);
+    }
+}